@@ -0,0 +1,56 @@
+//! Times the fused anti-alias chains against the separate filter calls they replaced, per sample of one channel.
+//!
+//! `cargo run --release --example anti_alias_bench --no-default-features`
+
+use std::f64::consts::PI;
+use std::hint::black_box;
+use std::time::Instant;
+
+use pitch_shifter::{anti_alias::AntiAlias, processor::Quality};
+use real_time_fir_iir_filters::{iir::third::ThirdOrderButterworthFilter, Filter};
+
+const RATE: f64 = 44100.0;
+const SAMPLES: usize = 1 << 22;
+
+/// Runs `f` over a noisy input and returns the time it took per sample, in nanoseconds.
+fn time(mut f: impl FnMut(f64) -> f64) -> f64
+{
+    let start = Instant::now();
+    let mut x = 1.0f64;
+    for _ in 0..SAMPLES
+    {
+        x = black_box(f(black_box((x*12.9898).sin())));
+    }
+    start.elapsed().as_nanos() as f64/SAMPLES as f64
+}
+
+fn main()
+{
+    let (omega_ceil, omega_floor) = (RATE*PI*0.8, 2.0*PI*60.0);
+    for aliasing in Quality::VARIANTS
+    {
+        // Input and output chains, like the shifter runs on every sample
+        let mut ceilings = [(); 2].map(|()| [(); Quality::VARIANT_COUNT].map(|()| ThirdOrderButterworthFilter::new(omega_ceil)));
+        let mut floors = [(); 2].map(|()| ThirdOrderButterworthFilter::new(omega_floor));
+        let separate = time(|x| {
+            ceilings.iter_mut()
+                .zip(floors.iter_mut())
+                .fold(x, |x, (ceilings, floor)| {
+                    let z = ceilings[..aliasing.stages()].iter_mut()
+                        .fold(x, |z, ceiling| ceiling.filter(RATE, z)[0]);
+                    floor.filter(RATE, z)[3]
+                })
+        });
+
+        let mut fused = [(); 2].map(|()| AntiAlias::new());
+        for fused in fused.iter_mut()
+        {
+            fused.set_cutoffs(RATE, omega_ceil, omega_floor);
+        }
+        let fused = time(|x| fused.iter_mut()
+            .fold(x, |x, fused| fused.filter(aliasing, x))
+        );
+
+        println!("{} stage(s): separate {:.1} ns, fused {:.1} ns per sample", aliasing.stages(), separate, fused);
+    }
+}
@@ -0,0 +1,58 @@
+//! Captures the impulse response of the library core at unity pitch as a WAV, for null tests or as a convolution IR of its coloration.
+//!
+//! `cargo run --example impulse_response --no-default-features -- [path]`
+
+use std::{fs, io};
+
+use pitch_shifter::{processor::{AudioProcessor, Settings}, PitchShifter};
+
+const RATE: f64 = 44100.0;
+
+/// Encodes a mono 32-bit float WAV.
+fn wav(rate: u32, x: &[f64]) -> Vec<u8>
+{
+    let data_size = (x.len()*4) as u32;
+    let mut wav = Vec::with_capacity(44 + x.len()*4);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // IEEE float, one channel
+    wav.extend_from_slice(&3u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&rate.to_le_bytes());
+    wav.extend_from_slice(&(rate*4).to_le_bytes());
+    wav.extend_from_slice(&4u16.to_le_bytes());
+    wav.extend_from_slice(&32u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for x in x
+    {
+        wav.extend_from_slice(&(*x as f32).to_le_bytes());
+    }
+    wav
+}
+
+fn main() -> io::Result<()>
+{
+    let path = std::env::args().nth(1).unwrap_or_else(|| "impulse_response.wav".to_string());
+
+    let mut shifter = PitchShifter::<1>::new(RATE);
+    shifter.configure(&Settings::default());
+    shifter.set_pitch(0.0);
+
+    // Twice the latency, so the tail after the main peak is in there too
+    let latency = shifter.latency();
+    let response = shifter.impulse_response(RATE, 0, 2*latency + 1);
+
+    let peak = response.iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    println!("Peak at sample {}, latency {} samples", peak, latency);
+
+    fs::write(&path, wav(RATE as u32, &response))?;
+    println!("Wrote {}", path);
+    Ok(())
+}
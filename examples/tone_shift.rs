@@ -0,0 +1,43 @@
+//! Shifts a generated sine up a fifth with the library core and checks where it ended up.
+//!
+//! `cargo run --example tone_shift --no-default-features`
+
+use std::f64::consts::TAU;
+
+use pitch_shifter::{processor::AudioProcessor, PitchShifter};
+
+const RATE: f64 = 44100.0;
+const FREQUENCY: f64 = 440.0;
+const FIFTH: f64 = 7.0/12.0;
+
+/// Estimates the frequency of a roughly sinusoidal signal from its rising zero crossings.
+fn frequency(x: &[f64]) -> f64
+{
+    let crossings: Vec<usize> = x.windows(2)
+        .enumerate()
+        .filter(|(_, w)| w[0] < 0.0 && w[1] >= 0.0)
+        .map(|(i, _)| i)
+        .collect();
+    let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+    (crossings.len() - 1) as f64*RATE/(last - first) as f64
+}
+
+fn main()
+{
+    let mut shifter = PitchShifter::<1>::new(RATE);
+    shifter.set_sample_rate(RATE);
+    shifter.set_pitch(FIFTH);
+
+    let mut block: Vec<f64> = (0..RATE as usize)
+        .map(|n| (TAU*FREQUENCY*n as f64/RATE).sin()*0.5)
+        .collect();
+    shifter.process_block(RATE, 0, &mut block);
+
+    // Skip the first half second, while the window fills up and the filters settle
+    let measured = frequency(&block[block.len()/2..]);
+    let expected = FREQUENCY*2.0f64.powf(FIFTH);
+    println!("{} Hz in, {:.1} Hz out, expected {:.1} Hz", FREQUENCY, measured, expected);
+
+    let cents = 1200.0*(measured/expected).log2();
+    assert!(cents.abs() < 20.0, "{} Hz is {:.1} cents off", measured, cents);
+}
@@ -0,0 +1,31 @@
+#ifndef PITCH_SHIFTER_H
+#define PITCH_SHIFTER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle. Build the crate with `--features capi` to export these symbols. */
+typedef struct PitchShifter PitchShifter;
+
+/* Returns NULL on failure, including a rate that isn't finite and positive. */
+PitchShifter *pitch_shifter_new(double rate);
+void pitch_shifter_free(PitchShifter *shifter);
+
+/* All of these return false if the handle is NULL or the call failed. */
+/* Fails on a rate that isn't finite and positive, leaving the shifter as it was. */
+bool pitch_shifter_set_sample_rate(PitchShifter *shifter, double rate);
+/* Pitch shift in octaves. */
+bool pitch_shifter_set_pitch(PitchShifter *shifter, double octaves);
+bool pitch_shifter_reset(PitchShifter *shifter);
+/* Processes `length` samples of `channel` (0 or 1) in place. Returns false for any other channel. */
+bool pitch_shifter_process(PitchShifter *shifter, size_t channel, float *samples, size_t length);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,35 @@
+/// First order all-pass, `H(z) = (a + z^-1)/(1 + a z^-1)`. Needs `|a| < 1` to be stable. At `a = 1` it's the identity in theory,
+/// but its pole sits on the unit circle and anything it picks up at Nyquist rings forever.
+#[derive(Clone, Copy)]
+pub struct AllPass
+{
+    pub a: f64,
+    x1: f64,
+    y1: f64
+}
+
+impl AllPass
+{
+    pub fn new(a: f64) -> Self
+    {
+        Self {
+            a,
+            x1: 0.0,
+            y1: 0.0
+        }
+    }
+
+    pub fn filter(&mut self, x: f64) -> f64
+    {
+        let y = self.a*x + self.x1 - self.a*self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+
+    pub fn reset(&mut self)
+    {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
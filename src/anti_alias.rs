@@ -0,0 +1,152 @@
+use crate::processor::Quality;
+
+/// Third order Butterworth low-pass or high-pass, discretized with the bilinear transform without prewarping, the same as
+/// `ThirdOrderButterworthFilter`. Keeps its coefficients between samples and only works out the output it's used for.
+#[derive(Clone, Copy)]
+struct Section
+{
+    b: [f64; 4],
+    a: [f64; 3],
+    w: [f64; 3]
+}
+
+impl Section
+{
+    const IDENTITY: Self = Self {
+        b: [1.0, 0.0, 0.0, 0.0],
+        a: [0.0; 3],
+        w: [0.0; 3]
+    };
+
+    fn set(&mut self, rate: f64, omega: f64, high_pass: bool)
+    {
+        let (r, o) = (rate, omega);
+        let (r2, o2) = (r*r, o*o);
+        let (r3, o3) = (r2*r, o2*o);
+        let a0 = 8.0*r3 + 8.0*r2*o + 4.0*r*o2 + o3;
+        self.a = [
+            (-24.0*r3 - 8.0*r2*o + 4.0*r*o2 + 3.0*o3)/a0,
+            (24.0*r3 - 8.0*r2*o - 4.0*r*o2 + 3.0*o3)/a0,
+            (-8.0*r3 + 8.0*r2*o - 4.0*r*o2 + o3)/a0
+        ];
+        self.b = if high_pass
+        {
+            [1.0, -3.0, 3.0, -1.0].map(|b| b*8.0*r3/a0)
+        }
+        else
+        {
+            [1.0, 3.0, 3.0, 1.0].map(|b| b*o3/a0)
+        };
+    }
+
+    /// Direct form II, like `ThirdOrderButterworthFilter`, so a gliding cutoff moves the response the same way.
+    fn filter(&mut self, x: f64) -> f64
+    {
+        let Self {b, a, w} = self;
+        let v = x - a[0]*w[0] - a[1]*w[1] - a[2]*w[2];
+        let y = b[0]*v + b[1]*w[0] + b[2]*w[1] + b[3]*w[2];
+        *w = [v, w[0], w[1]];
+        y
+    }
+}
+
+/// One anti-alias band-limiting chain, one or more third order low-pass stages on the ceiling and a third order high-pass on the
+/// floor, run as a single cascade.
+///
+/// Matches cascading `ThirdOrderButterworthFilter`s at the same cutoffs, but only recomputes the coefficients when a cutoff
+/// moves, instead of on every sample of every filter.
+#[derive(Clone, Copy)]
+pub struct AntiAlias
+{
+    /// The floor, then a ceiling for each step of [`Quality`].
+    sections: [Section; Quality::VARIANT_COUNT + 1],
+    /// Angular ceiling and floor the coefficients are for, and the rate.
+    cutoffs: [f64; 3]
+}
+
+impl AntiAlias
+{
+    pub fn new() -> Self
+    {
+        Self {
+            sections: [Section::IDENTITY; Quality::VARIANT_COUNT + 1],
+            cutoffs: [f64::NAN; 3]
+        }
+    }
+
+    /// Moves the cutoffs, in radians per second. Cheap when nothing changed.
+    pub fn set_cutoffs(&mut self, rate: f64, omega_ceil: f64, omega_floor: f64)
+    {
+        let cutoffs = [omega_ceil, omega_floor, rate];
+        if cutoffs == self.cutoffs
+        {
+            return
+        }
+        self.cutoffs = cutoffs;
+        let [floor, ceilings @ ..] = &mut self.sections;
+        floor.set(rate, omega_floor, true);
+        for ceiling in ceilings.iter_mut()
+        {
+            ceiling.set(rate, omega_ceil, false);
+        }
+    }
+
+    /// Band-limits one sample, with as many ceiling stages as `aliasing` asks for.
+    pub fn filter(&mut self, aliasing: Quality, x: f64) -> f64
+    {
+        self.sections[..=aliasing.stages()].iter_mut()
+            .fold(x, |x, section| section.filter(x))
+    }
+
+    pub fn reset(&mut self)
+    {
+        for section in self.sections.iter_mut()
+        {
+            section.w = [0.0; 3];
+        }
+    }
+}
+
+impl Default for AntiAlias
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::f64::consts::PI;
+
+    use real_time_fir_iir_filters::{iir::third::ThirdOrderButterworthFilter, Filter};
+
+    use crate::processor::Quality;
+
+    use super::AntiAlias;
+
+    const RATE: f64 = 44100.0;
+
+    #[test]
+    fn impulse_response_matches_the_separate_filters()
+    {
+        let (omega_ceil, omega_floor) = (RATE*PI*0.7, 2.0*PI*60.0);
+        for aliasing in Quality::VARIANTS
+        {
+            let mut fused = AntiAlias::new();
+            fused.set_cutoffs(RATE, omega_ceil, omega_floor);
+            let mut ceilings = [(); Quality::VARIANT_COUNT].map(|()| ThirdOrderButterworthFilter::new(omega_ceil));
+            let mut floor = ThirdOrderButterworthFilter::new(omega_floor);
+            for n in 0..4096
+            {
+                let x = if n == 0 {1.0} else {0.0};
+                let z = ceilings[..aliasing.stages()].iter_mut()
+                    .fold(x, |z, ceiling| ceiling.filter(RATE, z)[0]);
+                let [_, _, _, separate] = floor.filter(RATE, z);
+                let fused = fused.filter(aliasing, x);
+                assert!((fused - separate).abs() < 1e-9, "{} against {} at sample {}", fused, separate, n);
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! C ABI for the [`PitchShifter`] core, see `include/pitch_shifter.h`.
+//!
+//! The shifter is handed out as an opaque pointer. None of the functions let a panic unwind into the caller;
+//! a panic makes the function return null or `false` instead.
+
+use std::{panic::{self, AssertUnwindSafe}, ptr, slice};
+
+use crate::{processor::AudioProcessor, PitchShifter, CHANNEL_COUNT};
+
+/// Creates a shifter running at `rate` Hz. Returns null on failure, including a rate that isn't finite and positive.
+#[no_mangle]
+pub extern "C" fn pitch_shifter_new(rate: f64) -> *mut PitchShifter
+{
+    match panic::catch_unwind(|| PitchShifter::try_new(rate))
+    {
+        Ok(Ok(shifter)) => Box::into_raw(Box::new(shifter)),
+        _ => ptr::null_mut()
+    }
+}
+
+/// # Safety
+///
+/// `shifter` must be null or a pointer returned by [`pitch_shifter_new`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_shifter_free(shifter: *mut PitchShifter)
+{
+    if !shifter.is_null()
+    {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(shifter))));
+    }
+}
+
+/// Fails on a rate that isn't finite and positive, leaving the shifter at the rate it was at.
+///
+/// # Safety
+///
+/// `shifter` must be null or a live pointer returned by [`pitch_shifter_new`].
+#[no_mangle]
+pub unsafe extern "C" fn pitch_shifter_set_sample_rate(shifter: *mut PitchShifter, rate: f64) -> bool
+{
+    if !(rate.is_finite() && rate > 0.0)
+    {
+        return false
+    }
+    with_shifter(shifter, |shifter| shifter.set_sample_rate(rate))
+}
+
+/// Sets the pitch shift in octaves.
+///
+/// # Safety
+///
+/// `shifter` must be null or a live pointer returned by [`pitch_shifter_new`].
+#[no_mangle]
+pub unsafe extern "C" fn pitch_shifter_set_pitch(shifter: *mut PitchShifter, octaves: f64) -> bool
+{
+    with_shifter(shifter, |shifter| shifter.set_pitch(octaves))
+}
+
+/// # Safety
+///
+/// `shifter` must be null or a live pointer returned by [`pitch_shifter_new`].
+#[no_mangle]
+pub unsafe extern "C" fn pitch_shifter_reset(shifter: *mut PitchShifter) -> bool
+{
+    with_shifter(shifter, |shifter| shifter.reset())
+}
+
+/// Processes `length` samples of `channel` in place.
+///
+/// # Safety
+///
+/// `shifter` must be null or a live pointer returned by [`pitch_shifter_new`], and `samples` must point to
+/// `length` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_shifter_process(shifter: *mut PitchShifter, channel: usize, samples: *mut f32, length: usize) -> bool
+{
+    if samples.is_null()
+    {
+        return false
+    }
+    let samples = slice::from_raw_parts_mut(samples, length);
+    let mut ok = false;
+    let called = with_shifter(shifter, |shifter| {
+        if channel >= CHANNEL_COUNT
+        {
+            return
+        }
+        let rate = shifter.rate();
+        for x in samples.iter_mut()
+        {
+            *x = shifter.process_sample(rate, channel, *x as f64) as f32;
+        }
+        ok = true;
+    });
+    called && ok
+}
+
+unsafe fn with_shifter(shifter: *mut PitchShifter, f: impl FnOnce(&mut PitchShifter)) -> bool
+{
+    match shifter.as_mut()
+    {
+        Some(shifter) => panic::catch_unwind(AssertUnwindSafe(|| f(shifter))).is_ok(),
+        None => false
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::ptr;
+
+    use crate::CHANNEL_COUNT;
+
+    use super::{pitch_shifter_free, pitch_shifter_new, pitch_shifter_process, pitch_shifter_reset, pitch_shifter_set_pitch, pitch_shifter_set_sample_rate};
+
+    #[test]
+    fn round_trip_through_the_c_abi()
+    {
+        unsafe
+        {
+            let shifter = pitch_shifter_new(44100.0);
+            assert!(!shifter.is_null());
+            assert!(pitch_shifter_set_pitch(shifter, 1.0));
+            let mut samples = [0.5f32; 256];
+            assert!(pitch_shifter_process(shifter, 1, samples.as_mut_ptr(), samples.len()));
+            assert!(samples.iter().all(|x| x.is_finite()));
+            assert!(!pitch_shifter_process(shifter, CHANNEL_COUNT, samples.as_mut_ptr(), samples.len()));
+            assert!(!pitch_shifter_process(shifter, 0, ptr::null_mut(), 0));
+            assert!(pitch_shifter_reset(shifter));
+            pitch_shifter_free(shifter);
+        }
+    }
+
+    #[test]
+    fn invalid_sample_rates_are_rejected()
+    {
+        assert!(pitch_shifter_new(0.0).is_null());
+        unsafe
+        {
+            let shifter = pitch_shifter_new(44100.0);
+            for rate in [0.0, -44100.0, f64::NAN, f64::INFINITY]
+            {
+                assert!(!pitch_shifter_set_sample_rate(shifter, rate));
+            }
+            assert_eq!((*shifter).rate(), 44100.0);
+            assert!(pitch_shifter_set_sample_rate(shifter, 48000.0));
+            assert_eq!((*shifter).rate(), 48000.0);
+            pitch_shifter_free(shifter);
+
+            assert!(!pitch_shifter_set_sample_rate(ptr::null_mut(), 48000.0));
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::f64::consts::TAU;
+
+use real_time_fir_iir_filters::{iir::second::SecondOrderButterworthFilter, Filter};
+
+/// Fourth order Linkwitz-Riley crossover. The two bands it splits into sum back to an all-pass of the input.
+#[derive(Clone, Copy)]
+pub struct LinkwitzRiley
+{
+    low: [SecondOrderButterworthFilter<f64>; 2],
+    high: [SecondOrderButterworthFilter<f64>; 2]
+}
+
+impl LinkwitzRiley
+{
+    pub fn new(omega: f64) -> Self
+    {
+        Self {
+            low: [SecondOrderButterworthFilter::new(omega); 2],
+            high: [SecondOrderButterworthFilter::new(omega); 2]
+        }
+    }
+
+    /// Splits `x` into `[low, high]`.
+    pub fn split(&mut self, rate: f64, x: f64) -> [f64; 2]
+    {
+        let [low, _, _] = self.low[0].filter(rate, x);
+        let [low, _, _] = self.low[1].filter(rate, low);
+        let [_, _, high] = self.high[0].filter(rate, x);
+        let [_, _, high] = self.high[1].filter(rate, high);
+        [low, high]
+    }
+
+    /// Passes `x` through the same phase shift as the split, without splitting it.
+    pub fn all_pass(&mut self, rate: f64, x: f64) -> f64
+    {
+        let [low, high] = self.split(rate, x);
+        low + high
+    }
+}
+
+/// Smoothed mean square level of a signal in the bands below, between and above two crossovers, for meters.
+///
+/// The bands are split by second order filters, soft enough that the bands overlap around the crossovers. Good for reading the
+/// balance, not for exact band energies.
+#[derive(Clone, Copy)]
+pub struct BandMeter
+{
+    low: SecondOrderButterworthFilter<f64>,
+    high: SecondOrderButterworthFilter<f64>,
+    energy: [f64; 3]
+}
+
+impl BandMeter
+{
+    /// `crossovers` in Hz.
+    pub fn new(crossovers: [f64; 2]) -> Self
+    {
+        let [low, high] = crossovers.map(|f| SecondOrderButterworthFilter::new(f*TAU));
+        Self {
+            low,
+            high,
+            energy: [0.0; 3]
+        }
+    }
+
+    /// Meters one sample, averaging over about `1/alpha` samples.
+    pub fn push(&mut self, rate: f64, x: f64, alpha: f64)
+    {
+        let [low, _, _] = self.low.filter(rate, x);
+        let [_, _, high] = self.high.filter(rate, x);
+        for (energy, x) in self.energy.iter_mut()
+            .zip([low, x - low - high, high])
+        {
+            *energy += (x*x - *energy)*alpha;
+        }
+    }
+
+    /// Mean square level in the low, mid and high bands.
+    pub fn energy(&self) -> [f64; 3]
+    {
+        self.energy
+    }
+
+    pub fn reset(&mut self)
+    {
+        *self = Self::new([self.low.omega, self.high.omega].map(|omega| omega/TAU));
+    }
+}
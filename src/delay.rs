@@ -0,0 +1,46 @@
+/// Delay line of a fixed maximum length, which can be read at any delay up to that length.
+#[derive(Clone)]
+pub struct DelayLine
+{
+    buffer: Vec<f64>,
+    i: usize
+}
+
+impl DelayLine
+{
+    pub fn new(capacity: usize) -> Self
+    {
+        Self {
+            buffer: vec![0.0; capacity + 1],
+            i: 0
+        }
+    }
+
+    pub fn capacity(&self) -> usize
+    {
+        self.buffer.len() - 1
+    }
+
+    /// Pushes `x` and returns the sample from `delay` samples ago. A delay of 0 returns `x`.
+    pub fn delay(&mut self, x: f64, delay: usize) -> f64
+    {
+        let n = self.buffer.len();
+        self.buffer[self.i] = x;
+        let y = self.buffer[(self.i + n - delay.min(n - 1)) % n];
+        self.i = (self.i + 1) % n;
+        y
+    }
+
+    /// Reads the sample pushed `delay` samples ago without pushing anything. A delay of 0 reads the last sample pushed.
+    pub fn tap(&self, delay: usize) -> f64
+    {
+        let n = self.buffer.len();
+        self.buffer[(self.i + n - 1 - delay.min(n - 1)) % n]
+    }
+
+    pub fn reset(&mut self)
+    {
+        self.buffer.fill(0.0);
+        self.i = 0;
+    }
+}
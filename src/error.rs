@@ -0,0 +1,33 @@
+use std::fmt;
+
+/// What went wrong, for the operations that can fail.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PitchShifterError
+{
+    /// Sample rates have to be finite and positive.
+    InvalidSampleRate(f64),
+    /// The channel is past the number of channels the shifter was built for.
+    ChannelOutOfRange {
+        channel: usize,
+        channel_count: usize
+    },
+    /// Preset data that is cut short or otherwise malformed.
+    InvalidPreset {
+        length: usize
+    }
+}
+
+impl fmt::Display for PitchShifterError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Self::InvalidSampleRate(rate) => write!(f, "invalid sample rate {} Hz", rate),
+            Self::ChannelOutOfRange {channel, channel_count} => write!(f, "channel {} out of range for {} channels", channel, channel_count),
+            Self::InvalidPreset {length} => write!(f, "preset data of {} bytes is malformed", length)
+        }
+    }
+}
+
+impl std::error::Error for PitchShifterError {}
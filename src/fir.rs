@@ -0,0 +1,60 @@
+use std::f64::consts::PI;
+
+/// Linear-phase FIR filter of odd length `N`, delaying its input by `(N - 1)/2` samples.
+#[derive(Clone, Copy)]
+pub struct FirFilter<const N: usize>
+{
+    pub b: [f64; N],
+    w: [f64; N],
+    i: usize
+}
+
+impl<const N: usize> FirFilter<N>
+{
+    pub fn new(b: [f64; N]) -> Self
+    {
+        Self {
+            b,
+            w: [0.0; N],
+            i: 0
+        }
+    }
+
+    /// Hann-windowed sinc band-pass between the angular frequencies `omega_low` and `omega_high`.
+    pub fn band_pass(rate: f64, omega_low: f64, omega_high: f64) -> [f64; N]
+    {
+        let w_low = (omega_low/rate).clamp(0.0, PI);
+        let w_high = (omega_high/rate).clamp(w_low, PI);
+        let m = (N - 1) as f64/2.0;
+        core::array::from_fn(|n| {
+            let t = n as f64 - m;
+            let h = if t == 0.0
+            {
+                (w_high - w_low)/PI
+            }
+            else
+            {
+                ((w_high*t).sin() - (w_low*t).sin())/(PI*t)
+            };
+            let window = 0.5 - 0.5*(2.0*PI*n as f64/(N - 1) as f64).cos();
+            h*window
+        })
+    }
+
+    pub fn filter(&mut self, x: f64) -> f64
+    {
+        self.w[self.i] = x;
+        let y = self.b.iter()
+            .zip(self.w[..=self.i].iter().rev().chain(self.w[self.i + 1..].iter().rev()))
+            .map(|(b, w)| b*w)
+            .sum();
+        self.i = (self.i + 1) % N;
+        y
+    }
+
+    pub fn reset(&mut self)
+    {
+        self.w = [0.0; N];
+        self.i = 0;
+    }
+}
@@ -0,0 +1,94 @@
+use crate::delay::DelayLine;
+
+/// Lookahead peak limiter. The input is delayed by the lookahead while the gain already reacts to it, ramping down over the
+/// lookahead so each peak is met by the time it comes out, without a step in the gain.
+#[derive(Clone)]
+pub struct Limiter
+{
+    delay: DelayLine,
+    gain: f64
+}
+
+impl Limiter
+{
+    /// Makes a limiter with a lookahead of `lookahead` samples.
+    pub fn new(lookahead: usize) -> Self
+    {
+        Self {
+            delay: DelayLine::new(lookahead),
+            gain: 1.0
+        }
+    }
+
+    pub fn lookahead(&self) -> usize
+    {
+        self.delay.capacity()
+    }
+
+    /// Limits `x` to `ceiling`, returning the sample from one lookahead ago. `release` is how much the gain may recover by per
+    /// sample once the peaks have passed, as the factor an envelope would decay by. An infinite ceiling just delays.
+    pub fn limit(&mut self, x: f64, ceiling: f64, release: f64) -> f64
+    {
+        let lookahead = self.lookahead();
+        let y = self.delay.delay(x, lookahead);
+        // Every sample still in the delay ramps the gain down from unity as it goes in to just what it needs as it comes out
+        let ramp = if ceiling.is_finite()
+        {
+            (0..=lookahead).map(|age| {
+                    let peak = self.delay.tap(age).abs();
+                    if peak > ceiling
+                    {
+                        1.0 - (1.0 - ceiling/peak)*(age + 1) as f64/(lookahead + 1) as f64
+                    }
+                    else
+                    {
+                        1.0
+                    }
+                }).fold(1.0, f64::min)
+        }
+        else
+        {
+            1.0
+        };
+        self.gain = (self.gain/release).min(ramp);
+        // Rounding can leave a peak an ulp over
+        (y*self.gain).clamp(-ceiling, ceiling)
+    }
+
+    pub fn reset(&mut self)
+    {
+        self.delay.reset();
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::Limiter;
+
+    const LOOKAHEAD: usize = 64;
+    const RELEASE: f64 = 0.999;
+
+    #[test]
+    fn ramps_down_to_meet_a_peak()
+    {
+        let mut limiter = Limiter::new(LOOKAHEAD);
+        let gains: Vec<f64> = (0..1024).map(|n| if n == 512 {2.0} else {0.5})
+            .map(|x| limiter.limit(x, 1.0, RELEASE))
+            .enumerate()
+            .map(|(n, y)| {
+                // The peak itself comes out at exactly the ceiling
+                if n == 512 + LOOKAHEAD
+                {
+                    assert!((y - 1.0).abs() < 1e-12, "peak came out at {}", y);
+                }
+                y/if n == 512 + LOOKAHEAD {2.0} else {0.5}
+            }).collect();
+        let attack = 0.5/(LOOKAHEAD + 1) as f64;
+        for gain in gains.windows(2)
+        {
+            assert!(gain[0] - gain[1] <= attack + 1e-12, "gain dropped from {} to {}", gain[0], gain[1]);
+        }
+    }
+}
@@ -1,40 +1,1041 @@
+use std::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+
 use vst::prelude::PluginParameters;
 use vst::util::AtomicFloat;
 
+use crate::preset;
+use crate::processor::{Algorithm, DcBin, Quality};
+use crate::resampler::InternalRate;
+use crate::tuner::note;
+use crate::{PitchShifterError, CHANNEL_COUNT, WINDOW_LENGTH};
+
 pub const PITCH_PER_FINE_PITCH: f32 = 1.0/12.0;
 pub const OCTAVES_PER_UNIT_PITCH: f32 = 1.0;
-pub const CENTS_PER_UNIT_PITCH: f32 = 12.0*100.0*OCTAVES_PER_UNIT_PITCH;
+pub const SEMITONES_PER_UNIT_PITCH: f32 = 12.0*OCTAVES_PER_UNIT_PITCH;
+pub const CENTS_PER_UNIT_PITCH: f32 = 100.0*SEMITONES_PER_UNIT_PITCH;
 pub const PITCH_MAX: f32 = 1.0/OCTAVES_PER_UNIT_PITCH;
 pub const PITCH_MIN: f32 = -1.0/OCTAVES_PER_UNIT_PITCH;
+pub const BAND_COUNT_MAX: usize = 3;
+pub const COMPRESSOR_THRESHOLD_MIN: f32 = -60.0;
+pub const COMPRESSOR_THRESHOLD_MAX: f32 = 0.0;
+pub const COMPRESSOR_RATIO_MIN: f32 = 1.0;
+pub const COMPRESSOR_RATIO_MAX: f32 = 20.0;
+pub const WET_GAIN_MIN: f32 = -2.0;
+pub const WET_GAIN_MAX: f32 = 2.0;
+/// Marks a control that isn't bound to any MIDI CC.
+pub const NO_CC: u8 = u8::MAX;
+
+/// Channel routing gains, where `routing[i][j]` is how much of channel `j` goes into channel `i`.
+pub type Routing = [[f32; CHANNEL_COUNT]; CHANNEL_COUNT];
+
+/// Routing that passes every channel straight through.
+pub const ROUTING_IDENTITY: Routing = {
+    let mut routing = [[0.0; CHANNEL_COUNT]; CHANNEL_COUNT];
+    let mut i = 0;
+    while i < CHANNEL_COUNT
+    {
+        routing[i][i] = 1.0;
+        i += 1;
+    }
+    routing
+};
+
+/// Routing doing `b` first, then `a`.
+pub fn routing_product(a: &Routing, b: &Routing) -> Routing
+{
+    let mut product = [[0.0; CHANNEL_COUNT]; CHANNEL_COUNT];
+    for (product, a) in product.iter_mut()
+        .zip(a.iter())
+    {
+        for (gain, b) in a.iter()
+            .zip(b.iter())
+        {
+            for (product, b) in product.iter_mut()
+                .zip(b.iter())
+            {
+                *product += gain*b;
+            }
+        }
+    }
+    product
+}
+
+/// How the plugin handles the stereo channels, on top of the input and output routing.
+///
+/// Each mode is an input routing into the shifter and an output routing back out, see [`StereoMode::routing`]. The dry goes the same
+/// way, so modes that decode what they encode leave it untouched.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum StereoMode
+{
+    /// Left and right are processed as they are.
+    #[default]
+    Stereo,
+    /// Both channels get the sum of left and right, at half gain.
+    MonoSum,
+    /// Mid and side are processed in place of left and right, and turned back into left and right afterwards.
+    MidSide,
+    /// Left and right are processed as they are, and come out swapped.
+    Swapped,
+    /// Like mid-side, but the side comes back at twice the gain.
+    Wide
+}
+
+// Spelled out rather than left to the nightly-only variant_count, and checked against it with the nightly feature
+#[cfg(feature = "nightly")]
+const _: () = assert!(core::mem::variant_count::<StereoMode>() == StereoMode::VARIANT_COUNT);
+
+impl StereoMode
+{
+    pub const VARIANT_COUNT: usize = Self::VARIANTS.len();
+    pub const VARIANTS: [Self; 5] = [
+        Self::Stereo,
+        Self::MonoSum,
+        Self::MidSide,
+        Self::Swapped,
+        Self::Wide
+    ];
+    pub const NAMES: [&'static str; Self::VARIANT_COUNT] = [
+        "Stereo",
+        "Mono Sum",
+        "Mid-Side",
+        "Swapped",
+        "Wide"
+    ];
+
+    /// Input and output routing of the mode, in the same layout as [`BasicFilterParameters::routing`].
+    pub fn routing(self) -> [Routing; 2]
+    {
+        // Written out for two channels, like the modes themselves
+        match self
+        {
+            Self::Stereo => [ROUTING_IDENTITY, ROUTING_IDENTITY],
+            Self::MonoSum => [[[0.5, 0.5], [0.5, 0.5]], ROUTING_IDENTITY],
+            Self::MidSide => [[[0.5, 0.5], [0.5, -0.5]], [[1.0, 1.0], [1.0, -1.0]]],
+            Self::Swapped => [ROUTING_IDENTITY, [[0.0, 1.0], [1.0, 0.0]]],
+            Self::Wide => [[[0.5, 0.5], [0.5, -0.5]], [[1.0, 2.0], [1.0, -2.0]]]
+        }
+    }
+}
+pub const AUTO_TUNE_SPEED_MAX: f32 = 500.0;
+pub const TILT_MIN: f32 = -12.0;
+pub const TILT_MAX: f32 = 12.0;
+pub const ENVELOPE_ATTACK_MIN: f32 = 1.0;
+pub const ENVELOPE_ATTACK_MAX: f32 = 100.0;
+pub const ENVELOPE_RELEASE_MIN: f32 = 10.0;
+pub const ENVELOPE_RELEASE_MAX: f32 = 1000.0;
+pub const CEILING_MIN: f32 = -12.0;
+pub const CEILING_MAX: f32 = 12.0;
+pub const CV_DEPTH_MAX: f32 = 2.0;
+pub const GATE_THRESHOLD_MIN: f32 = -120.0;
+pub const GATE_THRESHOLD_MAX: f32 = 0.0;
+pub const GATE_SMOOTHING_MAX: f32 = 500.0;
+pub const PRE_DELAY_MAX: f32 = 500.0;
+pub const DRIVE_MAX: f32 = 24.0;
+pub const HAAS_MAX: f32 = 30.0;
+pub const MIDI_CHANNEL_NAMES: [&str; 17] = ["Omni", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"];
+pub const LOW_CUT_MIN: f32 = 1.0;
+pub const LOW_CUT_MAX: f32 = 32.0;
+pub const NOISE_GATE_THRESHOLD_MIN: f32 = -90.0;
+pub const NOISE_GATE_THRESHOLD_MAX: f32 = 0.0;
+pub const NOISE_GATE_HYSTERESIS_MAX: f32 = 24.0;
+pub const TRIM_MIN: f32 = -12.0;
+pub const TRIM_MAX: f32 = 12.0;
+pub const WOW_DEPTH_MAX: f32 = 50.0;
+pub const WOW_RATE_MIN: f32 = 0.1;
+pub const WOW_RATE_MAX: f32 = 4.0;
+pub const FLUTTER_DEPTH_MAX: f32 = 10.0;
+pub const FLUTTER_RATE_MIN: f32 = 4.0;
+pub const FLUTTER_RATE_MAX: f32 = 20.0;
+pub const CUTOFF_GLIDE_MIN: f32 = 1.0;
+pub const CUTOFF_GLIDE_MAX: f32 = 200.0;
+
+/// Number of frequency bands selected by the normalized value of the Bands control.
+pub fn band_count(value: f32) -> usize
+{
+    1 + enum_index(value, BAND_COUNT_MAX)
+}
+
+/// Index of the option picked by a normalized value, for a control with `count` options.
+pub fn enum_index(value: f32, count: usize) -> usize
+{
+    ((value*(count - 1) as f32).round() as usize).min(count - 1)
+}
+
+/// Normalized value picking option `index` of a control with `count` options. The inverse of [`enum_index`].
+pub fn enum_value(index: usize, count: usize) -> f32
+{
+    if count > 1 {index.min(count - 1) as f32/(count - 1) as f32} else {0.0}
+}
+
+/// How a control's value is interpreted, which decides how it's displayed.
+#[derive(Clone, Copy)]
+pub enum ControlKind
+{
+    Continuous,
+    Bool,
+    Enum(&'static [&'static str])
+}
 
 #[derive(Clone, Copy)]
 pub enum Control
 {
     Pitch,
     PitchFine,
-    Mix
+    Mix,
+    Algorithm,
+    Bypass,
+    Bands,
+    PitchLow,
+    PitchMid,
+    PitchHigh,
+    Compressor,
+    CompressorThreshold,
+    CompressorRatio,
+    WetGain,
+    Phase,
+    Info,
+    InputStageOnDry,
+    MidiLearn,
+    Decorrelation,
+    Aliasing,
+    DcBlock,
+    Limiter,
+    OctaveUp,
+    OctaveDown,
+    Normalize,
+    Tuner,
+    DetectedNote,
+    AutoTune,
+    AutoTuneStrength,
+    AutoTuneSpeed,
+    PitchStepped,
+    Tilt,
+    AutoTilt,
+    EnvelopeDepth,
+    EnvelopeAttack,
+    EnvelopeRelease,
+    EnvelopeInvert,
+    OfflineHq,
+    Ceiling,
+    CvDepth,
+    SendMode,
+    SpectralGate,
+    GateThreshold,
+    GateSmoothing,
+    PreDelay,
+    Drive,
+    SaturationPlacement,
+    Haas,
+    ResynthesisOnly,
+    MidiChannel,
+    Hop,
+    LowCut,
+    FineLink,
+    StereoMode,
+    ChannelLink,
+    NoiseGate,
+    NoiseGateThreshold,
+    NoiseGateHysteresis,
+    DcBin,
+    CpuGuard,
+    TrimLeft,
+    TrimRight,
+    InvertWetLeft,
+    InvertWetRight,
+    SpectralBalance,
+    WowDepth,
+    WowRate,
+    FlutterDepth,
+    FlutterRate,
+    SidechainGate,
+    SidechainThreshold,
+    SidechainAttack,
+    SidechainRelease,
+    CutoffGlide,
+    PitchLimit,
+    MatchGain,
+    HannWindow,
+    InternalRate
 }
 
+// Spelled out rather than left to the nightly-only variant_count, and checked against it with the nightly feature
+#[cfg(feature = "nightly")]
+const _: () = assert!(core::mem::variant_count::<Control>() == Control::VARIANT_COUNT);
+
 impl Control
 {
-    pub const VARIANT_COUNT: usize = core::mem::variant_count::<Self>();
-    pub const VARIANTS: [Self; Self::VARIANT_COUNT] = [
+    pub const VARIANT_COUNT: usize = Self::VARIANTS.len();
+    pub const VARIANTS: [Self; 77] = [
         Self::Pitch,
         Self::PitchFine,
-        Self::Mix
+        Self::Mix,
+        Self::Algorithm,
+        Self::Bypass,
+        Self::Bands,
+        Self::PitchLow,
+        Self::PitchMid,
+        Self::PitchHigh,
+        Self::Compressor,
+        Self::CompressorThreshold,
+        Self::CompressorRatio,
+        Self::WetGain,
+        Self::Phase,
+        Self::Info,
+        Self::InputStageOnDry,
+        Self::MidiLearn,
+        Self::Decorrelation,
+        Self::Aliasing,
+        Self::DcBlock,
+        Self::Limiter,
+        Self::OctaveUp,
+        Self::OctaveDown,
+        Self::Normalize,
+        Self::Tuner,
+        Self::DetectedNote,
+        Self::AutoTune,
+        Self::AutoTuneStrength,
+        Self::AutoTuneSpeed,
+        Self::PitchStepped,
+        Self::Tilt,
+        Self::AutoTilt,
+        Self::EnvelopeDepth,
+        Self::EnvelopeAttack,
+        Self::EnvelopeRelease,
+        Self::EnvelopeInvert,
+        Self::OfflineHq,
+        Self::Ceiling,
+        Self::CvDepth,
+        Self::SendMode,
+        Self::SpectralGate,
+        Self::GateThreshold,
+        Self::GateSmoothing,
+        Self::PreDelay,
+        Self::Drive,
+        Self::SaturationPlacement,
+        Self::Haas,
+        Self::ResynthesisOnly,
+        Self::MidiChannel,
+        Self::Hop,
+        Self::LowCut,
+        Self::FineLink,
+        Self::StereoMode,
+        Self::ChannelLink,
+        Self::NoiseGate,
+        Self::NoiseGateThreshold,
+        Self::NoiseGateHysteresis,
+        Self::DcBin,
+        Self::CpuGuard,
+        Self::TrimLeft,
+        Self::TrimRight,
+        Self::InvertWetLeft,
+        Self::InvertWetRight,
+        Self::SpectralBalance,
+        Self::WowDepth,
+        Self::WowRate,
+        Self::FlutterDepth,
+        Self::FlutterRate,
+        Self::SidechainGate,
+        Self::SidechainThreshold,
+        Self::SidechainAttack,
+        Self::SidechainRelease,
+        Self::CutoffGlide,
+        Self::PitchLimit,
+        Self::MatchGain,
+        Self::HannWindow,
+        Self::InternalRate
     ];
 
     pub fn from(i: i32) -> Self
     {
         Self::VARIANTS[i as usize]
     }
+
+    /// Name the control is stored under in presets. Unlike the index, this stays put when controls are added or reordered, so it
+    /// must never change once released.
+    pub fn key(self) -> &'static str
+    {
+        match self
+        {
+            Self::Pitch => "pitch",
+            Self::PitchFine => "pitch_fine",
+            Self::Mix => "mix",
+            Self::Algorithm => "algorithm",
+            Self::Bypass => "bypass",
+            Self::Bands => "bands",
+            Self::PitchLow => "pitch_low",
+            Self::PitchMid => "pitch_mid",
+            Self::PitchHigh => "pitch_high",
+            Self::Compressor => "compressor",
+            Self::CompressorThreshold => "compressor_threshold",
+            Self::CompressorRatio => "compressor_ratio",
+            Self::WetGain => "wet_gain",
+            Self::Phase => "phase",
+            Self::Info => "info",
+            Self::InputStageOnDry => "input_stage_on_dry",
+            Self::MidiLearn => "midi_learn",
+            Self::Decorrelation => "decorrelation",
+            Self::Aliasing => "aliasing",
+            Self::DcBlock => "dc_block",
+            Self::Limiter => "limiter",
+            Self::OctaveUp => "octave_up",
+            Self::OctaveDown => "octave_down",
+            Self::Normalize => "normalize",
+            Self::Tuner => "tuner",
+            Self::DetectedNote => "detected_note",
+            Self::AutoTune => "auto_tune",
+            Self::AutoTuneStrength => "auto_tune_strength",
+            Self::AutoTuneSpeed => "auto_tune_speed",
+            Self::PitchStepped => "pitch_stepped",
+            Self::Tilt => "tilt",
+            Self::AutoTilt => "auto_tilt",
+            Self::EnvelopeDepth => "envelope_depth",
+            Self::EnvelopeAttack => "envelope_attack",
+            Self::EnvelopeRelease => "envelope_release",
+            Self::EnvelopeInvert => "envelope_invert",
+            Self::OfflineHq => "offline_hq",
+            Self::Ceiling => "ceiling",
+            Self::CvDepth => "cv_depth",
+            Self::SendMode => "send_mode",
+            Self::SpectralGate => "spectral_gate",
+            Self::GateThreshold => "gate_threshold",
+            Self::GateSmoothing => "gate_smoothing",
+            Self::PreDelay => "pre_delay",
+            Self::Drive => "drive",
+            Self::SaturationPlacement => "saturation_placement",
+            Self::Haas => "haas",
+            Self::ResynthesisOnly => "resynthesis_only",
+            Self::MidiChannel => "midi_channel",
+            Self::Hop => "hop",
+            Self::LowCut => "low_cut",
+            Self::FineLink => "fine_link",
+            Self::StereoMode => "stereo_mode",
+            Self::ChannelLink => "channel_link",
+            Self::NoiseGate => "noise_gate",
+            Self::NoiseGateThreshold => "noise_gate_threshold",
+            Self::NoiseGateHysteresis => "noise_gate_hysteresis",
+            Self::DcBin => "dc_bin",
+            Self::CpuGuard => "cpu_guard",
+            Self::TrimLeft => "trim_left",
+            Self::TrimRight => "trim_right",
+            Self::InvertWetLeft => "invert_wet_left",
+            Self::InvertWetRight => "invert_wet_right",
+            Self::SpectralBalance => "spectral_balance",
+            Self::WowDepth => "wow_depth",
+            Self::WowRate => "wow_rate",
+            Self::FlutterDepth => "flutter_depth",
+            Self::FlutterRate => "flutter_rate",
+            Self::SidechainGate => "sidechain_gate",
+            Self::SidechainThreshold => "sidechain_threshold",
+            Self::SidechainAttack => "sidechain_attack",
+            Self::SidechainRelease => "sidechain_release",
+            Self::CutoffGlide => "cutoff_glide",
+            Self::PitchLimit => "pitch_limit",
+            Self::MatchGain => "match_gain",
+            Self::HannWindow => "hann_window",
+            Self::InternalRate => "internal_rate"
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self>
+    {
+        Self::VARIANTS.into_iter()
+            .find(|v| v.key() == key)
+    }
+
+    pub fn kind(self) -> ControlKind
+    {
+        match self
+        {
+            Self::Algorithm => ControlKind::Enum(&Algorithm::NAMES),
+            Self::Bands => ControlKind::Enum(&["1", "2", "3"]),
+            Self::Bypass | Self::Compressor | Self::InputStageOnDry | Self::MidiLearn | Self::DcBlock | Self::Limiter | Self::OctaveUp | Self::OctaveDown | Self::Normalize | Self::Tuner | Self::AutoTune | Self::PitchStepped | Self::AutoTilt | Self::EnvelopeInvert | Self::OfflineHq | Self::SendMode | Self::SpectralGate | Self::ResynthesisOnly | Self::ChannelLink | Self::NoiseGate | Self::CpuGuard | Self::InvertWetLeft | Self::InvertWetRight | Self::SidechainGate | Self::PitchLimit | Self::HannWindow => ControlKind::Bool,
+            Self::Phase => ControlKind::Enum(&["Minimum", "Linear"]),
+            Self::Aliasing => ControlKind::Enum(&Quality::NAMES),
+            Self::SaturationPlacement => ControlKind::Enum(&["Pre", "Post"]),
+            Self::MidiChannel => ControlKind::Enum(&MIDI_CHANNEL_NAMES),
+            Self::Hop => ControlKind::Enum(&["1", "2", "4", "8"]),
+            Self::FineLink => ControlKind::Enum(&["Relative", "Absolute"]),
+            Self::StereoMode => ControlKind::Enum(&StereoMode::NAMES),
+            Self::DcBin => ControlKind::Enum(&DcBin::NAMES),
+            Self::InternalRate => ControlKind::Enum(&InternalRate::NAMES),
+            _ => ControlKind::Continuous
+        }
+    }
+
+    /// Number of decimals shown in the control's parameter text.
+    pub fn precision(self) -> usize
+    {
+        match self
+        {
+            Self::Pitch => 0,
+            Self::PitchFine | Self::PitchLow | Self::PitchMid | Self::PitchHigh => 5,
+            Self::Mix | Self::WetGain => 0,
+            Self::CompressorThreshold | Self::CompressorRatio => 1,
+            Self::Decorrelation => 0,
+            Self::AutoTuneStrength => 0,
+            Self::AutoTuneSpeed => 0,
+            Self::Tilt => 1,
+            Self::EnvelopeDepth => 0,
+            Self::EnvelopeAttack => 0,
+            Self::EnvelopeRelease => 0,
+            Self::Ceiling => 1,
+            Self::CvDepth => 2,
+            Self::GateThreshold => 1,
+            Self::GateSmoothing => 0,
+            Self::PreDelay => 1,
+            Self::Drive => 1,
+            Self::Haas => 1,
+            Self::LowCut => 1,
+            Self::NoiseGateThreshold => 1,
+            Self::NoiseGateHysteresis => 1,
+            Self::TrimLeft => 1,
+            Self::TrimRight => 1,
+            Self::WowDepth => 1,
+            Self::WowRate => 2,
+            Self::FlutterDepth => 1,
+            Self::FlutterRate => 1,
+            Self::SidechainThreshold => 1,
+            Self::SidechainAttack => 0,
+            Self::SidechainRelease => 0,
+            Self::CutoffGlide => 0,
+            _ => 3
+        }
+    }
+
+    /// Bounds of the control in the units its parameter text and label are shown in, like cents or milliseconds.
+    ///
+    /// `None` for controls that aren't continuous, or that only display state.
+    pub fn range_display(self) -> Option<(f32, f32)>
+    {
+        match self
+        {
+            Self::Pitch => Some((PITCH_MIN*SEMITONES_PER_UNIT_PITCH, PITCH_MAX*SEMITONES_PER_UNIT_PITCH)),
+            Self::PitchFine => Some((PITCH_MIN*PITCH_PER_FINE_PITCH*CENTS_PER_UNIT_PITCH, PITCH_MAX*PITCH_PER_FINE_PITCH*CENTS_PER_UNIT_PITCH)),
+            Self::PitchLow | Self::PitchMid | Self::PitchHigh => Some((PITCH_MIN*CENTS_PER_UNIT_PITCH, PITCH_MAX*CENTS_PER_UNIT_PITCH)),
+            Self::Mix | Self::Decorrelation | Self::AutoTuneStrength | Self::EnvelopeDepth => Some((0.0, 100.0)),
+            Self::CompressorThreshold => Some((COMPRESSOR_THRESHOLD_MIN, COMPRESSOR_THRESHOLD_MAX)),
+            Self::CompressorRatio => Some((COMPRESSOR_RATIO_MIN, COMPRESSOR_RATIO_MAX)),
+            Self::WetGain => Some((WET_GAIN_MIN*100.0, WET_GAIN_MAX*100.0)),
+            Self::AutoTuneSpeed => Some((0.0, AUTO_TUNE_SPEED_MAX)),
+            Self::Tilt => Some((TILT_MIN, TILT_MAX)),
+            Self::EnvelopeAttack => Some((ENVELOPE_ATTACK_MIN, ENVELOPE_ATTACK_MAX)),
+            Self::EnvelopeRelease => Some((ENVELOPE_RELEASE_MIN, ENVELOPE_RELEASE_MAX)),
+            Self::Ceiling => Some((CEILING_MIN, CEILING_MAX)),
+            Self::CvDepth => Some((0.0, CV_DEPTH_MAX)),
+            Self::GateThreshold => Some((GATE_THRESHOLD_MIN, GATE_THRESHOLD_MAX)),
+            Self::GateSmoothing => Some((0.0, GATE_SMOOTHING_MAX)),
+            Self::PreDelay => Some((0.0, PRE_DELAY_MAX)),
+            Self::Drive => Some((0.0, DRIVE_MAX)),
+            Self::Haas => Some((0.0, HAAS_MAX)),
+            Self::LowCut => Some((LOW_CUT_MIN, LOW_CUT_MAX)),
+            Self::NoiseGateThreshold => Some((NOISE_GATE_THRESHOLD_MIN, NOISE_GATE_THRESHOLD_MAX)),
+            Self::NoiseGateHysteresis => Some((0.0, NOISE_GATE_HYSTERESIS_MAX)),
+            Self::TrimLeft | Self::TrimRight => Some((TRIM_MIN, TRIM_MAX)),
+            Self::WowDepth => Some((0.0, WOW_DEPTH_MAX)),
+            Self::WowRate => Some((WOW_RATE_MIN, WOW_RATE_MAX)),
+            Self::FlutterDepth => Some((0.0, FLUTTER_DEPTH_MAX)),
+            Self::FlutterRate => Some((FLUTTER_RATE_MIN, FLUTTER_RATE_MAX)),
+            Self::SidechainThreshold => Some((NOISE_GATE_THRESHOLD_MIN, NOISE_GATE_THRESHOLD_MAX)),
+            Self::SidechainAttack => Some((ENVELOPE_ATTACK_MIN, ENVELOPE_ATTACK_MAX)),
+            Self::SidechainRelease => Some((ENVELOPE_RELEASE_MIN, ENVELOPE_RELEASE_MAX)),
+            Self::CutoffGlide => Some((CUTOFF_GLIDE_MIN, CUTOFF_GLIDE_MAX)),
+            _ => None
+        }
+    }
+}
+
+/// The normalized value of every control at one point, see [`BasicFilterParameters::snapshot`].
+///
+/// Compares and hashes by the exact values, so two snapshots are only equal if restoring either gives the same state.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Snapshot
+{
+    bits: [u32; Control::VARIANT_COUNT]
+}
+
+impl Snapshot
+{
+    pub fn new(values: [f32; Control::VARIANT_COUNT]) -> Self
+    {
+        // Adding zero turns -0.0 into 0.0, so they compare equal like the floats do
+        Self {
+            bits: values.map(|value| (value + 0.0).to_bits())
+        }
+    }
+
+    pub fn get(&self, control: Control) -> f32
+    {
+        f32::from_bits(self.bits[control as usize])
+    }
+
+    pub fn values(&self) -> [f32; Control::VARIANT_COUNT]
+    {
+        self.bits.map(f32::from_bits)
+    }
 }
 
 pub struct BasicFilterParameters
 {
     pub pitch: AtomicFloat,
     pub pitch_fine: AtomicFloat,
-    pub mix: AtomicFloat
+    pub mix: AtomicFloat,
+    pub algorithm: AtomicFloat,
+    pub bypass: AtomicFloat,
+    pub bands: AtomicFloat,
+    pub pitch_low: AtomicFloat,
+    pub pitch_mid: AtomicFloat,
+    pub pitch_high: AtomicFloat,
+    pub compressor: AtomicFloat,
+    pub compressor_threshold: AtomicFloat,
+    pub compressor_ratio: AtomicFloat,
+    pub wet_gain: AtomicFloat,
+    pub phase: AtomicFloat,
+    /// Read-only state reported by the plugin, shown by [`Control::Info`].
+    pub sample_rate: AtomicFloat,
+    pub latency: AtomicFloat,
+    /// Hop the shifter is running at, after the CPU guard.
+    pub effective_hop: AtomicFloat,
+    /// Fundamental of the input in Hz, shown by [`Control::DetectedNote`]. Zero while the tuner is off or the input unpitched.
+    pub detected_pitch: AtomicFloat,
+    /// Whether the pitch limit is holding the pitch up, shown in the text of [`Control::Pitch`].
+    pub pitch_limited: AtomicFloat,
+    /// RMS level of the input after the trims and of the output, over the last few hundred milliseconds.
+    pub input_rms: AtomicFloat,
+    pub output_rms: AtomicFloat,
+    /// Mean square level of the input and wet in the low, mid and high bands, shown by [`Control::SpectralBalance`].
+    pub spectral_balance: [[AtomicFloat; 3]; 2],
+    pub input_stage_on_dry: AtomicFloat,
+    pub midi_learn: AtomicFloat,
+    /// The control last set, which MIDI learn binds the next CC to.
+    pub touched: AtomicI32,
+    /// The MIDI CC each control is bound to, or [`NO_CC`].
+    pub midi_cc: [AtomicU8; Control::VARIANT_COUNT],
+    /// Which inputs feed which shifter channels, and which shifter channels go to which outputs. See [`Routing`].
+    pub input_routing: [[AtomicFloat; CHANNEL_COUNT]; CHANNEL_COUNT],
+    pub output_routing: [[AtomicFloat; CHANNEL_COUNT]; CHANNEL_COUNT],
+    pub decorrelation: AtomicFloat,
+    pub aliasing: AtomicFloat,
+    pub dc_block: AtomicFloat,
+    pub limiter: AtomicFloat,
+    pub octave_up: AtomicFloat,
+    pub octave_down: AtomicFloat,
+    pub normalize: AtomicFloat,
+    pub tuner: AtomicFloat,
+    pub auto_tune: AtomicFloat,
+    pub auto_tune_strength: AtomicFloat,
+    pub auto_tune_speed: AtomicFloat,
+    pub pitch_stepped: AtomicFloat,
+    pub tilt: AtomicFloat,
+    pub auto_tilt: AtomicFloat,
+    pub envelope_depth: AtomicFloat,
+    pub envelope_attack: AtomicFloat,
+    pub envelope_release: AtomicFloat,
+    pub envelope_invert: AtomicFloat,
+    pub offline_hq: AtomicFloat,
+    pub ceiling: AtomicFloat,
+    pub cv_depth: AtomicFloat,
+    pub send_mode: AtomicFloat,
+    pub spectral_gate: AtomicFloat,
+    pub gate_threshold: AtomicFloat,
+    pub gate_smoothing: AtomicFloat,
+    pub pre_delay: AtomicFloat,
+    pub drive: AtomicFloat,
+    pub saturation_placement: AtomicFloat,
+    pub haas: AtomicFloat,
+    pub resynthesis_only: AtomicFloat,
+    pub midi_channel: AtomicFloat,
+    pub hop: AtomicFloat,
+    pub low_cut: AtomicFloat,
+    pub fine_link: AtomicFloat,
+    pub stereo_mode: AtomicFloat,
+    pub channel_link: AtomicFloat,
+    pub noise_gate: AtomicFloat,
+    pub noise_gate_threshold: AtomicFloat,
+    pub noise_gate_hysteresis: AtomicFloat,
+    pub dc_bin: AtomicFloat,
+    pub cpu_guard: AtomicFloat,
+    pub trim_left: AtomicFloat,
+    pub trim_right: AtomicFloat,
+    pub invert_wet_left: AtomicFloat,
+    pub invert_wet_right: AtomicFloat,
+    pub wow_depth: AtomicFloat,
+    pub wow_rate: AtomicFloat,
+    pub flutter_depth: AtomicFloat,
+    pub flutter_rate: AtomicFloat,
+    pub sidechain_gate: AtomicFloat,
+    pub sidechain_threshold: AtomicFloat,
+    pub sidechain_attack: AtomicFloat,
+    pub sidechain_release: AtomicFloat,
+    pub cutoff_glide: AtomicFloat,
+    pub pitch_limit: AtomicFloat,
+    pub hann_window: AtomicFloat,
+    pub internal_rate: AtomicFloat
+}
+
+impl BasicFilterParameters
+{
+    /// Sets every control back to its default value, and clears the routing and the MIDI CC bindings. Doesn't count as touching
+    /// any of the controls for MIDI learn.
+    pub fn reset_to_defaults(&self)
+    {
+        let defaults = Self::default();
+        for v in Control::VARIANTS
+        {
+            self.set_value(v as i32, defaults.get_parameter(v as i32));
+        }
+        self.set_routing(ROUTING_IDENTITY, ROUTING_IDENTITY);
+        for midi_cc in self.midi_cc.iter()
+        {
+            midi_cc.store(NO_CC, Ordering::Relaxed);
+        }
+    }
+
+    /// Like [`set_parameter`](PluginParameters::set_parameter), but without counting as touching the control for MIDI learn.
+    pub(crate) fn set_value(&self, index: i32, value: f32)
+    {
+        match Control::from(index)
+        {
+            Control::Pitch => self.pitch.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
+            Control::PitchFine => self.pitch_fine.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
+            Control::Mix => self.mix.set(value),
+            Control::Algorithm => self.algorithm.set(value),
+            Control::Bypass => self.bypass.set(value),
+            Control::Bands => self.bands.set(value),
+            Control::PitchLow => self.pitch_low.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
+            Control::PitchMid => self.pitch_mid.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
+            Control::PitchHigh => self.pitch_high.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
+            Control::Compressor => self.compressor.set(value),
+            Control::CompressorThreshold => self.compressor_threshold.set(value*(COMPRESSOR_THRESHOLD_MAX - COMPRESSOR_THRESHOLD_MIN) + COMPRESSOR_THRESHOLD_MIN),
+            Control::CompressorRatio => self.compressor_ratio.set(value*(COMPRESSOR_RATIO_MAX - COMPRESSOR_RATIO_MIN) + COMPRESSOR_RATIO_MIN),
+            Control::WetGain => self.wet_gain.set(value*(WET_GAIN_MAX - WET_GAIN_MIN) + WET_GAIN_MIN),
+            Control::Phase => self.phase.set(value),
+            Control::Info => {},
+            Control::InputStageOnDry => self.input_stage_on_dry.set(value),
+            Control::MidiLearn => self.midi_learn.set(value),
+            Control::Decorrelation => self.decorrelation.set(value),
+            Control::Aliasing => self.aliasing.set(value),
+            Control::DcBlock => self.dc_block.set(value),
+            Control::Limiter => self.limiter.set(value),
+            Control::OctaveUp => self.octave_up.set(value),
+            Control::OctaveDown => self.octave_down.set(value),
+            Control::Normalize => self.normalize.set(value),
+            Control::Tuner => self.tuner.set(value),
+            Control::DetectedNote => {},
+            Control::AutoTune => self.auto_tune.set(value),
+            Control::AutoTuneStrength => self.auto_tune_strength.set(value),
+            Control::AutoTuneSpeed => self.auto_tune_speed.set(value*AUTO_TUNE_SPEED_MAX),
+            Control::PitchStepped => self.pitch_stepped.set(value),
+            Control::Tilt => self.tilt.set(value*(TILT_MAX - TILT_MIN) + TILT_MIN),
+            Control::AutoTilt => self.auto_tilt.set(value),
+            Control::EnvelopeDepth => self.envelope_depth.set(value),
+            Control::EnvelopeAttack => self.envelope_attack.set(value*(ENVELOPE_ATTACK_MAX - ENVELOPE_ATTACK_MIN) + ENVELOPE_ATTACK_MIN),
+            Control::EnvelopeRelease => self.envelope_release.set(value*(ENVELOPE_RELEASE_MAX - ENVELOPE_RELEASE_MIN) + ENVELOPE_RELEASE_MIN),
+            Control::EnvelopeInvert => self.envelope_invert.set(value),
+            Control::OfflineHq => self.offline_hq.set(value),
+            Control::Ceiling => self.ceiling.set(value*(CEILING_MAX - CEILING_MIN) + CEILING_MIN),
+            Control::CvDepth => self.cv_depth.set(value*CV_DEPTH_MAX),
+            Control::SendMode => self.send_mode.set(value),
+            Control::SpectralGate => self.spectral_gate.set(value),
+            Control::GateThreshold => self.gate_threshold.set(value*(GATE_THRESHOLD_MAX - GATE_THRESHOLD_MIN) + GATE_THRESHOLD_MIN),
+            Control::GateSmoothing => self.gate_smoothing.set(value*GATE_SMOOTHING_MAX),
+            Control::PreDelay => self.pre_delay.set(value*PRE_DELAY_MAX),
+            Control::Drive => self.drive.set(value*DRIVE_MAX),
+            Control::SaturationPlacement => self.saturation_placement.set(value),
+            Control::Haas => self.haas.set(value*HAAS_MAX),
+            Control::ResynthesisOnly => self.resynthesis_only.set(value),
+            Control::MidiChannel => self.midi_channel.set(value),
+            Control::Hop => self.hop.set(value),
+            Control::LowCut => self.low_cut.set(value*(LOW_CUT_MAX - LOW_CUT_MIN) + LOW_CUT_MIN),
+            Control::FineLink => self.fine_link.set(value),
+            Control::StereoMode => self.stereo_mode.set(value),
+            Control::ChannelLink => self.channel_link.set(value),
+            Control::NoiseGate => self.noise_gate.set(value),
+            Control::NoiseGateThreshold => self.noise_gate_threshold.set(value*(NOISE_GATE_THRESHOLD_MAX - NOISE_GATE_THRESHOLD_MIN) + NOISE_GATE_THRESHOLD_MIN),
+            Control::NoiseGateHysteresis => self.noise_gate_hysteresis.set(value*NOISE_GATE_HYSTERESIS_MAX),
+            Control::DcBin => self.dc_bin.set(value),
+            Control::CpuGuard => self.cpu_guard.set(value),
+            Control::TrimLeft => self.trim_left.set(value*(TRIM_MAX - TRIM_MIN) + TRIM_MIN),
+            Control::TrimRight => self.trim_right.set(value*(TRIM_MAX - TRIM_MIN) + TRIM_MIN),
+            Control::InvertWetLeft => self.invert_wet_left.set(value),
+            Control::InvertWetRight => self.invert_wet_right.set(value),
+            Control::SpectralBalance => {},
+            Control::WowDepth => self.wow_depth.set(value*WOW_DEPTH_MAX),
+            Control::WowRate => self.wow_rate.set(value*(WOW_RATE_MAX - WOW_RATE_MIN) + WOW_RATE_MIN),
+            Control::FlutterDepth => self.flutter_depth.set(value*FLUTTER_DEPTH_MAX),
+            Control::FlutterRate => self.flutter_rate.set(value*(FLUTTER_RATE_MAX - FLUTTER_RATE_MIN) + FLUTTER_RATE_MIN),
+            Control::SidechainGate => self.sidechain_gate.set(value),
+            Control::SidechainThreshold => self.sidechain_threshold.set(value*(NOISE_GATE_THRESHOLD_MAX - NOISE_GATE_THRESHOLD_MIN) + NOISE_GATE_THRESHOLD_MIN),
+            Control::SidechainAttack => self.sidechain_attack.set(value*(ENVELOPE_ATTACK_MAX - ENVELOPE_ATTACK_MIN) + ENVELOPE_ATTACK_MIN),
+            Control::SidechainRelease => self.sidechain_release.set(value*(ENVELOPE_RELEASE_MAX - ENVELOPE_RELEASE_MIN) + ENVELOPE_RELEASE_MIN),
+            Control::CutoffGlide => self.cutoff_glide.set(value*(CUTOFF_GLIDE_MAX - CUTOFF_GLIDE_MIN) + CUTOFF_GLIDE_MIN),
+            Control::PitchLimit => self.pitch_limit.set(value),
+            Control::MatchGain => {},
+            Control::HannWindow => self.hann_window.set(value),
+            Control::InternalRate => self.internal_rate.set(value)
+        }
+    }
+
+    /// Captures the value of every control, normalized like [`get_parameter`](PluginParameters::get_parameter).
+    pub fn snapshot(&self) -> Snapshot
+    {
+        Snapshot::new(Control::VARIANTS.map(|v| self.get_parameter(v as i32)))
+    }
+
+    /// Sets every control back to a [`snapshot`](BasicFilterParameters::snapshot). Doesn't count as touching any of them for MIDI learn.
+    pub fn restore(&self, snapshot: &Snapshot)
+    {
+        for v in Control::VARIANTS
+        {
+            self.set_value(v as i32, snapshot.get(v));
+        }
+    }
+
+    /// Loads data from [`get_preset_data`](PluginParameters::get_preset_data), or from older versions of the plugin. Checks all of
+    /// it before changing anything.
+    pub fn try_load_preset_data(&self, data: &[u8]) -> Result<(), PitchShifterError>
+    {
+        preset::decode(self, data)
+    }
+
+    /// If MIDI learn is armed, binds `cc` to the last touched control and disarms. Returns whether it did.
+    pub fn learn_cc(&self, cc: u8) -> bool
+    {
+        let touched = self.touched.load(Ordering::Relaxed);
+        if self.midi_learn.get() < 0.5 || touched < 0
+        {
+            return false
+        }
+        for midi_cc in self.midi_cc.iter()
+        {
+            let _ = midi_cc.compare_exchange(cc, NO_CC, Ordering::Relaxed, Ordering::Relaxed);
+        }
+        self.midi_cc[touched as usize].store(cc, Ordering::Relaxed);
+        self.midi_learn.set(0.0);
+        true
+    }
+
+    /// Gain that would bring the output to the level of the input, from [`output_rms`](Self::output_rms) and
+    /// [`input_rms`](Self::input_rms), for comparing the wet and dry at the same loudness. 1 while either is silent.
+    pub fn match_gain(&self) -> f32
+    {
+        let (input, output) = (self.input_rms.get(), self.output_rms.get());
+        if input > 0.0 && output > 0.0 {input/output} else {1.0}
+    }
+
+    /// The input and output routing.
+    pub fn routing(&self) -> [Routing; 2]
+    {
+        [&self.input_routing, &self.output_routing].map(|routing| routing.each_ref().map(|row| row.each_ref().map(|gain| gain.get())))
+    }
+
+    pub fn set_routing(&self, input: Routing, output: Routing)
+    {
+        for (routing, gains) in [&self.input_routing, &self.output_routing].into_iter()
+            .zip([input, output])
+        {
+            for (gain, value) in routing.iter().flatten().zip(gains.into_iter().flatten())
+            {
+                gain.set(value);
+            }
+        }
+    }
+
+    /// Whether MIDI on `channel`, counting from 0, should be listened to.
+    pub fn listens_to(&self, channel: u8) -> bool
+    {
+        match enum_index(self.midi_channel.get(), MIDI_CHANNEL_NAMES.len())
+        {
+            0 => true,
+            midi_channel => midi_channel == channel as usize + 1
+        }
+    }
+
+    /// Moves every control bound to `cc` to the CC's value.
+    pub fn apply_cc(&self, cc: u8, value: u8)
+    {
+        for (v, midi_cc) in Control::VARIANTS.into_iter()
+            .zip(self.midi_cc.iter())
+        {
+            if midi_cc.load(Ordering::Relaxed) == cc
+            {
+                self.set_parameter(v as i32, value as f32/127.0);
+            }
+        }
+    }
+
+    /// Text for controls of [`ControlKind::Continuous`].
+    fn get_continuous_text(&self, index: i32) -> String
+    {
+        let precision = Control::from(index).precision();
+        match Control::from(index)
+        {
+            // Coarse is ignored when fine is absolute
+            Control::Pitch => {
+                let text = if enum_index(self.fine_link.get(), 2) == 1
+                {
+                    "-".to_string()
+                }
+                else if self.pitch_stepped.get() >= 0.5
+                {
+                    format!("{}", (self.pitch.get()*SEMITONES_PER_UNIT_PITCH).round())
+                }
+                else
+                {
+                    format!("{:.*}", precision, self.pitch.get()*SEMITONES_PER_UNIT_PITCH)
+                };
+                if self.pitch_limited.get() >= 0.5 {format!("{} (limited)", text)} else {text}
+            },
+            Control::PitchFine => format!("{:.*}", precision, self.pitch_fine.get()*PITCH_PER_FINE_PITCH*CENTS_PER_UNIT_PITCH),
+            Control::Mix => format!("{:.*}", precision, self.mix.get()*100.0),
+            Control::PitchLow => format!("{:.*}", precision, self.pitch_low.get()*CENTS_PER_UNIT_PITCH),
+            Control::PitchMid => format!("{:.*}", precision, self.pitch_mid.get()*CENTS_PER_UNIT_PITCH),
+            Control::PitchHigh => format!("{:.*}", precision, self.pitch_high.get()*CENTS_PER_UNIT_PITCH),
+            Control::CompressorThreshold => format!("{:.*}", precision, self.compressor_threshold.get()),
+            Control::CompressorRatio => format!("{:.*}", precision, self.compressor_ratio.get()),
+            Control::WetGain => format!("{:.*}", precision, self.wet_gain.get()*100.0),
+            Control::Info => format!("{} Hz, window {}, latency {} samples, hop {}", self.sample_rate.get(), WINDOW_LENGTH, self.latency.get(), self.effective_hop.get()),
+            Control::Decorrelation => format!("{:.*}", precision, self.decorrelation.get()*100.0),
+            Control::MatchGain => format!("{:+.1}", 20.0*self.match_gain().log10()),
+            Control::SpectralBalance => {
+                let [input, wet] = &self.spectral_balance;
+                let db = |bands: &[AtomicFloat; 3]| bands.iter()
+                    .map(|energy| format!("{:.0}", 10.0*(energy.get() as f64).max(1e-12).log10()))
+                    .collect::<Vec<_>>()
+                    .join("/");
+                format!("in {}, wet {} dB", db(input), db(wet))
+            },
+            Control::DetectedNote => if self.detected_pitch.get() > 0.0
+            {
+                let (note, cents) = note(self.detected_pitch.get() as f64);
+                format!("{} {:+.0} cents", note, cents)
+            }
+            else
+            {
+                "-".to_string()
+            },
+            Control::AutoTuneStrength => format!("{:.*}", precision, self.auto_tune_strength.get()*100.0),
+            Control::AutoTuneSpeed => format!("{:.*}", precision, self.auto_tune_speed.get()),
+            Control::Tilt => format!("{:.*}", precision, self.tilt.get()),
+            Control::EnvelopeDepth => format!("{:.*}", precision, self.envelope_depth.get()*100.0),
+            Control::EnvelopeAttack => format!("{:.*}", precision, self.envelope_attack.get()),
+            Control::EnvelopeRelease => format!("{:.*}", precision, self.envelope_release.get()),
+            Control::Ceiling => format!("{:.*}", precision, self.ceiling.get()),
+            Control::CvDepth => format!("{:.*}", precision, self.cv_depth.get()),
+            Control::GateThreshold => format!("{:.*}", precision, self.gate_threshold.get()),
+            Control::GateSmoothing => format!("{:.*}", precision, self.gate_smoothing.get()),
+            Control::PreDelay => format!("{:.*}", precision, self.pre_delay.get()),
+            Control::Drive => format!("{:.*}", precision, self.drive.get()),
+            Control::Haas => format!("{:.*}", precision, self.haas.get()),
+            Control::LowCut => format!("{:.*}", precision, self.low_cut.get()),
+            Control::NoiseGateThreshold => format!("{:.*}", precision, self.noise_gate_threshold.get()),
+            Control::NoiseGateHysteresis => format!("{:.*}", precision, self.noise_gate_hysteresis.get()),
+            Control::TrimLeft => format!("{:.*}", precision, self.trim_left.get()),
+            Control::TrimRight => format!("{:.*}", precision, self.trim_right.get()),
+            Control::WowDepth => format!("{:.*}", precision, self.wow_depth.get()),
+            Control::WowRate => format!("{:.*}", precision, self.wow_rate.get()),
+            Control::FlutterDepth => format!("{:.*}", precision, self.flutter_depth.get()),
+            Control::FlutterRate => format!("{:.*}", precision, self.flutter_rate.get()),
+            Control::SidechainThreshold => format!("{:.*}", precision, self.sidechain_threshold.get()),
+            Control::SidechainAttack => format!("{:.*}", precision, self.sidechain_attack.get()),
+            Control::SidechainRelease => format!("{:.*}", precision, self.sidechain_release.get()),
+            Control::CutoffGlide => format!("{:.*}", precision, self.cutoff_glide.get()),
+            _ => format!("{:.*}", precision, self.get_parameter(index))
+        }
+    }
+}
+
+impl Default for BasicFilterParameters
+{
+    fn default() -> Self
+    {
+        Self {
+            pitch: AtomicFloat::from(0.0),
+            pitch_fine: AtomicFloat::from(0.0),
+            mix: AtomicFloat::from(1.0),
+            algorithm: AtomicFloat::from(0.0),
+            bypass: AtomicFloat::from(0.0),
+            bands: AtomicFloat::from(0.0),
+            pitch_low: AtomicFloat::from(0.0),
+            pitch_mid: AtomicFloat::from(0.0),
+            pitch_high: AtomicFloat::from(0.0),
+            compressor: AtomicFloat::from(0.0),
+            compressor_threshold: AtomicFloat::from(-12.0),
+            compressor_ratio: AtomicFloat::from(4.0),
+            wet_gain: AtomicFloat::from(1.0),
+            phase: AtomicFloat::from(0.0),
+            sample_rate: AtomicFloat::from(44100.0),
+            latency: AtomicFloat::from(0.0),
+            effective_hop: AtomicFloat::from(1.0),
+            detected_pitch: AtomicFloat::from(0.0),
+            pitch_limited: AtomicFloat::from(0.0),
+            input_rms: AtomicFloat::from(0.0),
+            output_rms: AtomicFloat::from(0.0),
+            spectral_balance: [(); 2].map(|()| [(); 3].map(|()| AtomicFloat::from(0.0))),
+            input_stage_on_dry: AtomicFloat::from(0.0),
+            midi_learn: AtomicFloat::from(0.0),
+            touched: AtomicI32::new(-1),
+            midi_cc: [(); Control::VARIANT_COUNT].map(|()| AtomicU8::new(NO_CC)),
+            input_routing: ROUTING_IDENTITY.map(|row| row.map(AtomicFloat::from)),
+            output_routing: ROUTING_IDENTITY.map(|row| row.map(AtomicFloat::from)),
+            decorrelation: AtomicFloat::from(0.0),
+            aliasing: AtomicFloat::from(0.0),
+            dc_block: AtomicFloat::from(0.0),
+            limiter: AtomicFloat::from(0.0),
+            octave_up: AtomicFloat::from(0.0),
+            octave_down: AtomicFloat::from(0.0),
+            normalize: AtomicFloat::from(0.0),
+            tuner: AtomicFloat::from(0.0),
+            auto_tune: AtomicFloat::from(0.0),
+            auto_tune_strength: AtomicFloat::from(1.0),
+            auto_tune_speed: AtomicFloat::from(50.0),
+            pitch_stepped: AtomicFloat::from(0.0),
+            tilt: AtomicFloat::from(0.0),
+            auto_tilt: AtomicFloat::from(0.0),
+            envelope_depth: AtomicFloat::from(0.0),
+            envelope_attack: AtomicFloat::from(10.0),
+            envelope_release: AtomicFloat::from(200.0),
+            envelope_invert: AtomicFloat::from(0.0),
+            offline_hq: AtomicFloat::from(0.0),
+            ceiling: AtomicFloat::from(6.0),
+            cv_depth: AtomicFloat::from(0.0),
+            send_mode: AtomicFloat::from(0.0),
+            spectral_gate: AtomicFloat::from(0.0),
+            gate_threshold: AtomicFloat::from(-80.0),
+            gate_smoothing: AtomicFloat::from(20.0),
+            pre_delay: AtomicFloat::from(0.0),
+            drive: AtomicFloat::from(0.0),
+            saturation_placement: AtomicFloat::from(0.0),
+            haas: AtomicFloat::from(0.0),
+            resynthesis_only: AtomicFloat::from(0.0),
+            midi_channel: AtomicFloat::from(0.0),
+            hop: AtomicFloat::from(0.0),
+            low_cut: AtomicFloat::from(8.0),
+            fine_link: AtomicFloat::from(0.0),
+            stereo_mode: AtomicFloat::from(0.0),
+            channel_link: AtomicFloat::from(0.0),
+            noise_gate: AtomicFloat::from(0.0),
+            noise_gate_threshold: AtomicFloat::from(-60.0),
+            noise_gate_hysteresis: AtomicFloat::from(6.0),
+            dc_bin: AtomicFloat::from(0.0),
+            cpu_guard: AtomicFloat::from(0.0),
+            trim_left: AtomicFloat::from(0.0),
+            trim_right: AtomicFloat::from(0.0),
+            invert_wet_left: AtomicFloat::from(0.0),
+            invert_wet_right: AtomicFloat::from(0.0),
+            wow_depth: AtomicFloat::from(0.0),
+            wow_rate: AtomicFloat::from(0.5),
+            flutter_depth: AtomicFloat::from(0.0),
+            flutter_rate: AtomicFloat::from(8.0),
+            sidechain_gate: AtomicFloat::from(0.0),
+            sidechain_threshold: AtomicFloat::from(-30.0),
+            sidechain_attack: AtomicFloat::from(5.0),
+            sidechain_release: AtomicFloat::from(100.0),
+            cutoff_glide: AtomicFloat::from(10.0),
+            pitch_limit: AtomicFloat::from(0.0),
+            hann_window: AtomicFloat::from(0.0),
+            internal_rate: AtomicFloat::from(0.0)
+        }
+    }
 }
 
 impl PluginParameters for BasicFilterParameters
@@ -43,19 +1044,94 @@ impl PluginParameters for BasicFilterParameters
     {
         match Control::from(index)
         {
-            Control::Pitch => "cents".to_string(),
+            Control::Pitch => "semitones".to_string(),
             Control::PitchFine => "cents".to_string(),
-            Control::Mix => "%".to_string()
+            Control::Mix => "%".to_string(),
+            Control::Algorithm => "".to_string(),
+            Control::Bypass => "".to_string(),
+            Control::Bands => "".to_string(),
+            Control::PitchLow => "cents".to_string(),
+            Control::PitchMid => "cents".to_string(),
+            Control::PitchHigh => "cents".to_string(),
+            Control::Compressor => "".to_string(),
+            Control::CompressorThreshold => "dB".to_string(),
+            Control::CompressorRatio => ":1".to_string(),
+            Control::WetGain => "%".to_string(),
+            Control::Phase => "".to_string(),
+            Control::Info => "".to_string(),
+            Control::InputStageOnDry => "".to_string(),
+            Control::MidiLearn => "".to_string(),
+            Control::Decorrelation => "%".to_string(),
+            Control::Aliasing => "".to_string(),
+            Control::DcBlock => "".to_string(),
+            Control::Limiter => "".to_string(),
+            Control::OctaveUp => "".to_string(),
+            Control::OctaveDown => "".to_string(),
+            Control::Normalize => "".to_string(),
+            Control::Tuner => "".to_string(),
+            Control::DetectedNote => "".to_string(),
+            Control::AutoTune => "".to_string(),
+            Control::AutoTuneStrength => "%".to_string(),
+            Control::AutoTuneSpeed => "ms".to_string(),
+            Control::PitchStepped => "".to_string(),
+            Control::Tilt => "dB".to_string(),
+            Control::AutoTilt => "".to_string(),
+            Control::EnvelopeDepth => "%".to_string(),
+            Control::EnvelopeAttack => "ms".to_string(),
+            Control::EnvelopeRelease => "ms".to_string(),
+            Control::EnvelopeInvert => "".to_string(),
+            Control::OfflineHq => "".to_string(),
+            Control::Ceiling => "dBFS".to_string(),
+            Control::CvDepth => "oct".to_string(),
+            Control::SendMode => "".to_string(),
+            Control::SpectralGate => "".to_string(),
+            Control::GateThreshold => "dB".to_string(),
+            Control::GateSmoothing => "ms".to_string(),
+            Control::PreDelay => "ms".to_string(),
+            Control::Drive => "dB".to_string(),
+            Control::SaturationPlacement => "".to_string(),
+            Control::Haas => "ms".to_string(),
+            Control::ResynthesisOnly => "".to_string(),
+            Control::MidiChannel => "".to_string(),
+            Control::Hop => "".to_string(),
+            Control::LowCut => "bins".to_string(),
+            Control::FineLink => "".to_string(),
+            Control::StereoMode => "".to_string(),
+            Control::ChannelLink => "".to_string(),
+            Control::NoiseGate => "".to_string(),
+            Control::NoiseGateThreshold => "dB".to_string(),
+            Control::NoiseGateHysteresis => "dB".to_string(),
+            Control::DcBin => "".to_string(),
+            Control::CpuGuard => "".to_string(),
+            Control::TrimLeft => "dB".to_string(),
+            Control::TrimRight => "dB".to_string(),
+            Control::InvertWetLeft => "".to_string(),
+            Control::InvertWetRight => "".to_string(),
+            Control::SpectralBalance => "".to_string(),
+            Control::WowDepth => "cents".to_string(),
+            Control::WowRate => "Hz".to_string(),
+            Control::FlutterDepth => "cents".to_string(),
+            Control::FlutterRate => "Hz".to_string(),
+            Control::SidechainGate => "".to_string(),
+            Control::SidechainThreshold => "dB".to_string(),
+            Control::SidechainAttack => "ms".to_string(),
+            Control::SidechainRelease => "ms".to_string(),
+            Control::CutoffGlide => "ms".to_string(),
+            Control::PitchLimit => "".to_string(),
+            Control::MatchGain => "dB".to_string(),
+            Control::HannWindow => "".to_string(),
+            Control::InternalRate => "".to_string()
         }
     }
 
     fn get_parameter_text(&self, index: i32) -> String
     {
-        match Control::from(index)
+        let value = self.get_parameter(index);
+        match Control::from(index).kind()
         {
-            Control::Pitch => format!("{:.3}", (self.pitch.get() + self.pitch_fine.get()*PITCH_PER_FINE_PITCH)*CENTS_PER_UNIT_PITCH),
-            Control::PitchFine => format!("{:.3}", (self.pitch.get() + self.pitch_fine.get()*PITCH_PER_FINE_PITCH)*CENTS_PER_UNIT_PITCH),
-            Control::Mix => format!("{:.3}", self.mix.get()*100.0)
+            ControlKind::Continuous => self.get_continuous_text(index),
+            ControlKind::Bool => if value >= 0.5 {"On"} else {"Off"}.to_string(),
+            ControlKind::Enum(names) => names[enum_index(value, names.len())].to_string()
         }
     }
 
@@ -65,7 +1141,81 @@ impl PluginParameters for BasicFilterParameters
         {
             Control::Pitch => "Pitch".to_string(),
             Control::PitchFine => "Pitch (Fine)".to_string(),
-            Control::Mix => "Mix".to_string()
+            Control::Mix => "Mix".to_string(),
+            Control::Algorithm => "Algorithm".to_string(),
+            Control::Bypass => "Bypass".to_string(),
+            Control::Bands => "Bands".to_string(),
+            Control::PitchLow => "Pitch (Low Band)".to_string(),
+            Control::PitchMid => "Pitch (Mid Band)".to_string(),
+            Control::PitchHigh => "Pitch (High Band)".to_string(),
+            Control::Compressor => "Input Compressor".to_string(),
+            Control::CompressorThreshold => "Compressor Threshold".to_string(),
+            Control::CompressorRatio => "Compressor Ratio".to_string(),
+            Control::WetGain => "Wet Gain".to_string(),
+            Control::Phase => "Phase".to_string(),
+            Control::Info => "Info".to_string(),
+            Control::InputStageOnDry => "Input Stage On Dry".to_string(),
+            Control::MidiLearn => "MIDI Learn".to_string(),
+            Control::Decorrelation => "Decorrelation".to_string(),
+            Control::Aliasing => "Aliasing".to_string(),
+            Control::DcBlock => "DC Block".to_string(),
+            Control::Limiter => "Limiter".to_string(),
+            Control::OctaveUp => "Octave Up".to_string(),
+            Control::OctaveDown => "Octave Down".to_string(),
+            Control::Normalize => "Normalize".to_string(),
+            Control::Tuner => "Tuner".to_string(),
+            Control::DetectedNote => "Detected Note".to_string(),
+            Control::AutoTune => "Auto-Tune".to_string(),
+            Control::AutoTuneStrength => "Auto-Tune Strength".to_string(),
+            Control::AutoTuneSpeed => "Auto-Tune Speed".to_string(),
+            Control::PitchStepped => "Pitch Stepped".to_string(),
+            Control::Tilt => "Tilt".to_string(),
+            Control::AutoTilt => "Auto Tilt".to_string(),
+            Control::EnvelopeDepth => "Envelope Depth".to_string(),
+            Control::EnvelopeAttack => "Envelope Attack".to_string(),
+            Control::EnvelopeRelease => "Envelope Release".to_string(),
+            Control::EnvelopeInvert => "Envelope Invert".to_string(),
+            Control::OfflineHq => "Offline HQ".to_string(),
+            Control::Ceiling => "Ceiling".to_string(),
+            Control::CvDepth => "CV Depth".to_string(),
+            Control::SendMode => "Send Mode".to_string(),
+            Control::SpectralGate => "Spectral Gate".to_string(),
+            Control::GateThreshold => "Gate Threshold".to_string(),
+            Control::GateSmoothing => "Gate Smoothing".to_string(),
+            Control::PreDelay => "Pre-Delay".to_string(),
+            Control::Drive => "Drive".to_string(),
+            Control::SaturationPlacement => "Saturation".to_string(),
+            Control::Haas => "Haas".to_string(),
+            Control::ResynthesisOnly => "Resynthesis Only".to_string(),
+            Control::MidiChannel => "MIDI Channel".to_string(),
+            Control::Hop => "Hop".to_string(),
+            Control::LowCut => "Low Cut".to_string(),
+            Control::FineLink => "Fine Link".to_string(),
+            Control::StereoMode => "Stereo Mode".to_string(),
+            Control::ChannelLink => "Channel Link".to_string(),
+            Control::NoiseGate => "Noise Gate".to_string(),
+            Control::NoiseGateThreshold => "Noise Gate Threshold".to_string(),
+            Control::NoiseGateHysteresis => "Noise Gate Hysteresis".to_string(),
+            Control::DcBin => "DC Bin".to_string(),
+            Control::CpuGuard => "CPU Guard".to_string(),
+            Control::TrimLeft => "Trim (Left)".to_string(),
+            Control::TrimRight => "Trim (Right)".to_string(),
+            Control::InvertWetLeft => "Invert Wet (Left)".to_string(),
+            Control::InvertWetRight => "Invert Wet (Right)".to_string(),
+            Control::SpectralBalance => "Spectral Balance".to_string(),
+            Control::WowDepth => "Wow Depth".to_string(),
+            Control::WowRate => "Wow Rate".to_string(),
+            Control::FlutterDepth => "Flutter Depth".to_string(),
+            Control::FlutterRate => "Flutter Rate".to_string(),
+            Control::SidechainGate => "Sidechain Gate".to_string(),
+            Control::SidechainThreshold => "Sidechain Threshold".to_string(),
+            Control::SidechainAttack => "Sidechain Attack".to_string(),
+            Control::SidechainRelease => "Sidechain Release".to_string(),
+            Control::CutoffGlide => "Cutoff Glide".to_string(),
+            Control::PitchLimit => "Pitch Limit".to_string(),
+            Control::MatchGain => "Match Gain".to_string(),
+            Control::HannWindow => "Hann Window".to_string(),
+            Control::InternalRate => "Internal Rate".to_string()
         }
     }
 
@@ -76,18 +1226,91 @@ impl PluginParameters for BasicFilterParameters
         {
             Control::Pitch => (self.pitch.get() - PITCH_MIN)/(PITCH_MAX - PITCH_MIN),
             Control::PitchFine => (self.pitch_fine.get() - PITCH_MIN)/(PITCH_MAX - PITCH_MIN),
-            Control::Mix => self.mix.get()
+            Control::Mix => self.mix.get(),
+            Control::Algorithm => self.algorithm.get(),
+            Control::Bypass => self.bypass.get(),
+            Control::Bands => self.bands.get(),
+            Control::PitchLow => (self.pitch_low.get() - PITCH_MIN)/(PITCH_MAX - PITCH_MIN),
+            Control::PitchMid => (self.pitch_mid.get() - PITCH_MIN)/(PITCH_MAX - PITCH_MIN),
+            Control::PitchHigh => (self.pitch_high.get() - PITCH_MIN)/(PITCH_MAX - PITCH_MIN),
+            Control::Compressor => self.compressor.get(),
+            Control::CompressorThreshold => (self.compressor_threshold.get() - COMPRESSOR_THRESHOLD_MIN)/(COMPRESSOR_THRESHOLD_MAX - COMPRESSOR_THRESHOLD_MIN),
+            Control::CompressorRatio => (self.compressor_ratio.get() - COMPRESSOR_RATIO_MIN)/(COMPRESSOR_RATIO_MAX - COMPRESSOR_RATIO_MIN),
+            Control::WetGain => (self.wet_gain.get() - WET_GAIN_MIN)/(WET_GAIN_MAX - WET_GAIN_MIN),
+            Control::Phase => self.phase.get(),
+            Control::Info => 0.0,
+            Control::InputStageOnDry => self.input_stage_on_dry.get(),
+            Control::MidiLearn => self.midi_learn.get(),
+            Control::Decorrelation => self.decorrelation.get(),
+            Control::Aliasing => self.aliasing.get(),
+            Control::DcBlock => self.dc_block.get(),
+            Control::Limiter => self.limiter.get(),
+            Control::OctaveUp => self.octave_up.get(),
+            Control::OctaveDown => self.octave_down.get(),
+            Control::Normalize => self.normalize.get(),
+            Control::Tuner => self.tuner.get(),
+            Control::DetectedNote => 0.0,
+            Control::AutoTune => self.auto_tune.get(),
+            Control::AutoTuneStrength => self.auto_tune_strength.get(),
+            Control::AutoTuneSpeed => self.auto_tune_speed.get()/AUTO_TUNE_SPEED_MAX,
+            Control::PitchStepped => self.pitch_stepped.get(),
+            Control::Tilt => (self.tilt.get() - TILT_MIN)/(TILT_MAX - TILT_MIN),
+            Control::AutoTilt => self.auto_tilt.get(),
+            Control::EnvelopeDepth => self.envelope_depth.get(),
+            Control::EnvelopeAttack => (self.envelope_attack.get() - ENVELOPE_ATTACK_MIN)/(ENVELOPE_ATTACK_MAX - ENVELOPE_ATTACK_MIN),
+            Control::EnvelopeRelease => (self.envelope_release.get() - ENVELOPE_RELEASE_MIN)/(ENVELOPE_RELEASE_MAX - ENVELOPE_RELEASE_MIN),
+            Control::EnvelopeInvert => self.envelope_invert.get(),
+            Control::OfflineHq => self.offline_hq.get(),
+            Control::Ceiling => (self.ceiling.get() - CEILING_MIN)/(CEILING_MAX - CEILING_MIN),
+            Control::CvDepth => self.cv_depth.get()/CV_DEPTH_MAX,
+            Control::SendMode => self.send_mode.get(),
+            Control::SpectralGate => self.spectral_gate.get(),
+            Control::GateThreshold => (self.gate_threshold.get() - GATE_THRESHOLD_MIN)/(GATE_THRESHOLD_MAX - GATE_THRESHOLD_MIN),
+            Control::GateSmoothing => self.gate_smoothing.get()/GATE_SMOOTHING_MAX,
+            Control::PreDelay => self.pre_delay.get()/PRE_DELAY_MAX,
+            Control::Drive => self.drive.get()/DRIVE_MAX,
+            Control::SaturationPlacement => self.saturation_placement.get(),
+            Control::Haas => self.haas.get()/HAAS_MAX,
+            Control::ResynthesisOnly => self.resynthesis_only.get(),
+            Control::MidiChannel => self.midi_channel.get(),
+            Control::Hop => self.hop.get(),
+            Control::LowCut => (self.low_cut.get() - LOW_CUT_MIN)/(LOW_CUT_MAX - LOW_CUT_MIN),
+            Control::FineLink => self.fine_link.get(),
+            Control::StereoMode => self.stereo_mode.get(),
+            Control::ChannelLink => self.channel_link.get(),
+            Control::NoiseGate => self.noise_gate.get(),
+            Control::NoiseGateThreshold => (self.noise_gate_threshold.get() - NOISE_GATE_THRESHOLD_MIN)/(NOISE_GATE_THRESHOLD_MAX - NOISE_GATE_THRESHOLD_MIN),
+            Control::NoiseGateHysteresis => self.noise_gate_hysteresis.get()/NOISE_GATE_HYSTERESIS_MAX,
+            Control::DcBin => self.dc_bin.get(),
+            Control::CpuGuard => self.cpu_guard.get(),
+            Control::TrimLeft => (self.trim_left.get() - TRIM_MIN)/(TRIM_MAX - TRIM_MIN),
+            Control::TrimRight => (self.trim_right.get() - TRIM_MIN)/(TRIM_MAX - TRIM_MIN),
+            Control::InvertWetLeft => self.invert_wet_left.get(),
+            Control::InvertWetRight => self.invert_wet_right.get(),
+            Control::SpectralBalance => 0.0,
+            Control::WowDepth => self.wow_depth.get()/WOW_DEPTH_MAX,
+            Control::WowRate => (self.wow_rate.get() - WOW_RATE_MIN)/(WOW_RATE_MAX - WOW_RATE_MIN),
+            Control::FlutterDepth => self.flutter_depth.get()/FLUTTER_DEPTH_MAX,
+            Control::FlutterRate => (self.flutter_rate.get() - FLUTTER_RATE_MIN)/(FLUTTER_RATE_MAX - FLUTTER_RATE_MIN),
+            Control::SidechainGate => self.sidechain_gate.get(),
+            Control::SidechainThreshold => (self.sidechain_threshold.get() - NOISE_GATE_THRESHOLD_MIN)/(NOISE_GATE_THRESHOLD_MAX - NOISE_GATE_THRESHOLD_MIN),
+            Control::SidechainAttack => (self.sidechain_attack.get() - ENVELOPE_ATTACK_MIN)/(ENVELOPE_ATTACK_MAX - ENVELOPE_ATTACK_MIN),
+            Control::SidechainRelease => (self.sidechain_release.get() - ENVELOPE_RELEASE_MIN)/(ENVELOPE_RELEASE_MAX - ENVELOPE_RELEASE_MIN),
+            Control::CutoffGlide => (self.cutoff_glide.get() - CUTOFF_GLIDE_MIN)/(CUTOFF_GLIDE_MAX - CUTOFF_GLIDE_MIN),
+            Control::PitchLimit => self.pitch_limit.get(),
+            Control::MatchGain => 0.0,
+            Control::HannWindow => self.hann_window.get(),
+            Control::InternalRate => self.internal_rate.get()
         }
     }
     
     fn set_parameter(&self, index: i32, value: f32)
     {
-        match Control::from(index)
+        if !matches!(Control::from(index), Control::MidiLearn | Control::Info | Control::DetectedNote | Control::SpectralBalance | Control::MatchGain)
         {
-            Control::Pitch => self.pitch.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
-            Control::PitchFine => self.pitch_fine.set(value*(PITCH_MAX - PITCH_MIN) + PITCH_MIN),
-            Control::Mix => self.mix.set(value)
+            self.touched.store(index, Ordering::Relaxed);
         }
+        self.set_value(index, value)
     }
 
     fn change_preset(&self, _preset: i32) {}
@@ -103,13 +1326,12 @@ impl PluginParameters for BasicFilterParameters
     }
 
     fn can_be_automated(&self, index: i32) -> bool {
-        index < Control::VARIANTS.len() as i32
+        index < Control::VARIANTS.len() as i32 && !matches!(Control::from(index), Control::Info | Control::DetectedNote | Control::SpectralBalance | Control::MatchGain)
     }
 
     fn get_preset_data(&self) -> Vec<u8>
     {
-        Control::VARIANTS.map(|v| self.get_parameter(v as i32).to_le_bytes())
-            .concat()
+        preset::encode(self)
     }
 
     fn get_bank_data(&self) -> Vec<u8>
@@ -119,15 +1341,437 @@ impl PluginParameters for BasicFilterParameters
 
     fn load_preset_data(&self, data: &[u8])
     {
-        for (v, &b) in Control::VARIANTS.into_iter()
-            .zip(data.array_chunks())
-        {
-            self.set_parameter(v as i32, f32::from_le_bytes(b));
-        }
+        // Nowhere to report it to, so a bad preset just leaves the controls as they were
+        let _ = self.try_load_preset_data(data);
     }
 
     fn load_bank_data(&self, data: &[u8])
     {
         self.load_preset_data(data);
     }
-}
\ No newline at end of file
+}
+
+/// Every control's value in its own units, for setting up the plugin from code without going through normalized values.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Params
+{
+    /// Coarse pitch in octaves.
+    pub pitch: f32,
+    /// Fine pitch in cents.
+    pub pitch_fine: f32,
+    /// Wet ratio of the mix, between 0.0 and 1.0.
+    pub mix: f32,
+    pub algorithm: Algorithm,
+    pub bypass: bool,
+    /// Number of bands, from 1 to [`BAND_COUNT_MAX`].
+    pub bands: usize,
+    /// Band pitch offsets in octaves.
+    pub pitch_low: f32,
+    pub pitch_mid: f32,
+    pub pitch_high: f32,
+    pub compressor: bool,
+    /// In dB.
+    pub compressor_threshold: f32,
+    pub compressor_ratio: f32,
+    pub wet_gain: f32,
+    pub linear_phase: bool,
+    pub input_stage_on_dry: bool,
+    pub decorrelation: f32,
+    pub aliasing: Quality,
+    pub dc_block: bool,
+    pub limiter: bool,
+    pub octave_up: bool,
+    pub octave_down: bool,
+    pub normalize: bool,
+    pub tuner: bool,
+    pub auto_tune: bool,
+    pub auto_tune_strength: f32,
+    /// In milliseconds.
+    pub auto_tune_speed: f32,
+    pub pitch_stepped: bool,
+    /// In dB.
+    pub tilt: f32,
+    pub auto_tilt: bool,
+    pub envelope_depth: f32,
+    /// In milliseconds.
+    pub envelope_attack: f32,
+    /// In milliseconds.
+    pub envelope_release: f32,
+    pub envelope_invert: bool,
+    pub offline_hq: bool,
+    /// In dBFS.
+    pub ceiling: f32,
+    /// Pitch offset in octaves at full scale CV.
+    pub cv_depth: f32,
+    pub send_mode: bool,
+    pub spectral_gate: bool,
+    /// In dB, relative to a full scale sine.
+    pub gate_threshold: f32,
+    /// In milliseconds.
+    pub gate_smoothing: f32,
+    /// Delay of the wet in milliseconds.
+    pub pre_delay: f32,
+    /// Saturation drive in dB. Zero leaves the signal untouched.
+    pub drive: f32,
+    /// Saturate the resynthesis rather than the input to the analysis.
+    pub saturation_post: bool,
+    /// Delay of the right wet channel behind the left in milliseconds.
+    pub haas: f32,
+    pub resynthesis_only: bool,
+    /// MIDI channel from 1 to 16 to listen to, or 0 for all of them.
+    pub midi_channel: usize,
+    /// Samples between each resynthesis, 1, 2, 4 or 8.
+    pub hop: usize,
+    /// Anti-alias low cut in bins of the analysis window.
+    pub low_cut: f32,
+    /// Fine sets the shift alone instead of adding onto the coarse pitch.
+    pub fine_absolute: bool,
+    pub stereo_mode: StereoMode,
+    /// Keep the phase of every channel on the first channel's.
+    pub channel_link: bool,
+    pub noise_gate: bool,
+    /// Input level in dBFS the noise gate opens at.
+    pub noise_gate_threshold: f32,
+    /// How far in dB below the threshold the noise gate closes again.
+    pub noise_gate_hysteresis: f32,
+    pub dc_bin: DcBin,
+    /// Raise the hop on its own when processing gets close to overrunning the block.
+    pub cpu_guard: bool,
+    /// Input gain of the left channel in dB.
+    pub trim_left: f32,
+    /// Input gain of the right channel in dB.
+    pub trim_right: f32,
+    pub invert_wet_left: bool,
+    pub invert_wet_right: bool,
+    /// Depth in cents of the slow, drifting pitch wobble.
+    pub wow_depth: f32,
+    /// Rate in Hz of the wow, which wanders a little around it.
+    pub wow_rate: f32,
+    /// Depth in cents of the fast pitch wobble.
+    pub flutter_depth: f32,
+    /// Rate in Hz of the flutter.
+    pub flutter_rate: f32,
+    /// Gate the wet by the level of the sidechain input, if the host connected one.
+    pub sidechain_gate: bool,
+    /// Sidechain level in dBFS the wet is let through above.
+    pub sidechain_threshold: f32,
+    /// Attack in milliseconds of the sidechain envelope.
+    pub sidechain_attack: f32,
+    /// Release in milliseconds of the sidechain envelope.
+    pub sidechain_release: f32,
+    /// Time in milliseconds for the anti-alias cutoffs to follow a pitch change.
+    pub cutoff_glide: f32,
+    /// Keep the pitch from going so far down that the wet loses its low end.
+    pub pitch_limit: bool,
+    /// Hann window the analysis, resynthesizing from two overlapping heads.
+    pub hann_window: bool,
+    /// Rate to run the shifting at, resampled to and from the host's.
+    pub internal_rate: InternalRate
+}
+
+impl Params
+{
+    pub fn from_atomic(p: &BasicFilterParameters) -> Self
+    {
+        Self {
+            pitch: p.pitch.get()*OCTAVES_PER_UNIT_PITCH,
+            pitch_fine: p.pitch_fine.get()*PITCH_PER_FINE_PITCH*CENTS_PER_UNIT_PITCH,
+            mix: p.mix.get(),
+            algorithm: Algorithm::from(p.algorithm.get()),
+            bypass: p.bypass.get() >= 0.5,
+            bands: band_count(p.bands.get()),
+            pitch_low: p.pitch_low.get()*OCTAVES_PER_UNIT_PITCH,
+            pitch_mid: p.pitch_mid.get()*OCTAVES_PER_UNIT_PITCH,
+            pitch_high: p.pitch_high.get()*OCTAVES_PER_UNIT_PITCH,
+            compressor: p.compressor.get() >= 0.5,
+            compressor_threshold: p.compressor_threshold.get(),
+            compressor_ratio: p.compressor_ratio.get(),
+            wet_gain: p.wet_gain.get(),
+            linear_phase: enum_index(p.phase.get(), 2) == 1,
+            input_stage_on_dry: p.input_stage_on_dry.get() >= 0.5,
+            decorrelation: p.decorrelation.get(),
+            aliasing: Quality::VARIANTS[enum_index(p.aliasing.get(), Quality::VARIANT_COUNT)],
+            dc_block: p.dc_block.get() >= 0.5,
+            limiter: p.limiter.get() >= 0.5,
+            octave_up: p.octave_up.get() >= 0.5,
+            octave_down: p.octave_down.get() >= 0.5,
+            normalize: p.normalize.get() >= 0.5,
+            tuner: p.tuner.get() >= 0.5,
+            auto_tune: p.auto_tune.get() >= 0.5,
+            auto_tune_strength: p.auto_tune_strength.get(),
+            auto_tune_speed: p.auto_tune_speed.get(),
+            pitch_stepped: p.pitch_stepped.get() >= 0.5,
+            tilt: p.tilt.get(),
+            auto_tilt: p.auto_tilt.get() >= 0.5,
+            envelope_depth: p.envelope_depth.get(),
+            envelope_attack: p.envelope_attack.get(),
+            envelope_release: p.envelope_release.get(),
+            envelope_invert: p.envelope_invert.get() >= 0.5,
+            offline_hq: p.offline_hq.get() >= 0.5,
+            ceiling: p.ceiling.get(),
+            cv_depth: p.cv_depth.get(),
+            send_mode: p.send_mode.get() >= 0.5,
+            spectral_gate: p.spectral_gate.get() >= 0.5,
+            gate_threshold: p.gate_threshold.get(),
+            gate_smoothing: p.gate_smoothing.get(),
+            pre_delay: p.pre_delay.get(),
+            drive: p.drive.get(),
+            saturation_post: enum_index(p.saturation_placement.get(), 2) == 1,
+            haas: p.haas.get(),
+            resynthesis_only: p.resynthesis_only.get() >= 0.5,
+            midi_channel: enum_index(p.midi_channel.get(), MIDI_CHANNEL_NAMES.len()),
+            hop: 1 << enum_index(p.hop.get(), 4),
+            low_cut: p.low_cut.get(),
+            fine_absolute: enum_index(p.fine_link.get(), 2) == 1,
+            stereo_mode: StereoMode::VARIANTS[enum_index(p.stereo_mode.get(), StereoMode::VARIANT_COUNT)],
+            channel_link: p.channel_link.get() >= 0.5,
+            noise_gate: p.noise_gate.get() >= 0.5,
+            noise_gate_threshold: p.noise_gate_threshold.get(),
+            noise_gate_hysteresis: p.noise_gate_hysteresis.get(),
+            dc_bin: DcBin::VARIANTS[enum_index(p.dc_bin.get(), DcBin::VARIANT_COUNT)],
+            cpu_guard: p.cpu_guard.get() >= 0.5,
+            trim_left: p.trim_left.get(),
+            trim_right: p.trim_right.get(),
+            invert_wet_left: p.invert_wet_left.get() >= 0.5,
+            invert_wet_right: p.invert_wet_right.get() >= 0.5,
+            wow_depth: p.wow_depth.get(),
+            wow_rate: p.wow_rate.get(),
+            flutter_depth: p.flutter_depth.get(),
+            flutter_rate: p.flutter_rate.get(),
+            sidechain_gate: p.sidechain_gate.get() >= 0.5,
+            sidechain_threshold: p.sidechain_threshold.get(),
+            sidechain_attack: p.sidechain_attack.get(),
+            sidechain_release: p.sidechain_release.get(),
+            cutoff_glide: p.cutoff_glide.get(),
+            pitch_limit: p.pitch_limit.get() >= 0.5,
+            hann_window: p.hann_window.get() >= 0.5,
+            internal_rate: InternalRate::VARIANTS[enum_index(p.internal_rate.get(), InternalRate::VARIANT_COUNT)]
+        }
+    }
+
+    /// Sets every control to its value here, clamped to the control's range.
+    pub fn apply(&self, p: &BasicFilterParameters)
+    {
+        p.pitch.set((self.pitch/OCTAVES_PER_UNIT_PITCH).clamp(PITCH_MIN, PITCH_MAX));
+        p.pitch_fine.set((self.pitch_fine/(PITCH_PER_FINE_PITCH*CENTS_PER_UNIT_PITCH)).clamp(PITCH_MIN, PITCH_MAX));
+        p.mix.set(self.mix.clamp(0.0, 1.0));
+        p.algorithm.set(enum_value(self.algorithm as usize, Algorithm::VARIANT_COUNT));
+        p.bypass.set(if self.bypass {1.0} else {0.0});
+        p.bands.set(enum_value(self.bands.max(1) - 1, BAND_COUNT_MAX));
+        p.pitch_low.set((self.pitch_low/OCTAVES_PER_UNIT_PITCH).clamp(PITCH_MIN, PITCH_MAX));
+        p.pitch_mid.set((self.pitch_mid/OCTAVES_PER_UNIT_PITCH).clamp(PITCH_MIN, PITCH_MAX));
+        p.pitch_high.set((self.pitch_high/OCTAVES_PER_UNIT_PITCH).clamp(PITCH_MIN, PITCH_MAX));
+        p.compressor.set(if self.compressor {1.0} else {0.0});
+        p.compressor_threshold.set(self.compressor_threshold.clamp(COMPRESSOR_THRESHOLD_MIN, COMPRESSOR_THRESHOLD_MAX));
+        p.compressor_ratio.set(self.compressor_ratio.clamp(COMPRESSOR_RATIO_MIN, COMPRESSOR_RATIO_MAX));
+        p.wet_gain.set(self.wet_gain.clamp(WET_GAIN_MIN, WET_GAIN_MAX));
+        p.phase.set(enum_value(self.linear_phase as usize, 2));
+        p.input_stage_on_dry.set(if self.input_stage_on_dry {1.0} else {0.0});
+        p.decorrelation.set(self.decorrelation.clamp(0.0, 1.0));
+        p.aliasing.set(enum_value(self.aliasing as usize, Quality::VARIANT_COUNT));
+        p.dc_block.set(if self.dc_block {1.0} else {0.0});
+        p.limiter.set(if self.limiter {1.0} else {0.0});
+        p.octave_up.set(if self.octave_up {1.0} else {0.0});
+        p.octave_down.set(if self.octave_down {1.0} else {0.0});
+        p.normalize.set(if self.normalize {1.0} else {0.0});
+        p.tuner.set(if self.tuner {1.0} else {0.0});
+        p.auto_tune.set(if self.auto_tune {1.0} else {0.0});
+        p.auto_tune_strength.set(self.auto_tune_strength.clamp(0.0, 1.0));
+        p.auto_tune_speed.set(self.auto_tune_speed.clamp(0.0, AUTO_TUNE_SPEED_MAX));
+        p.pitch_stepped.set(if self.pitch_stepped {1.0} else {0.0});
+        p.tilt.set(self.tilt.clamp(TILT_MIN, TILT_MAX));
+        p.auto_tilt.set(if self.auto_tilt {1.0} else {0.0});
+        p.envelope_depth.set(self.envelope_depth.clamp(0.0, 1.0));
+        p.envelope_attack.set(self.envelope_attack.clamp(ENVELOPE_ATTACK_MIN, ENVELOPE_ATTACK_MAX));
+        p.envelope_release.set(self.envelope_release.clamp(ENVELOPE_RELEASE_MIN, ENVELOPE_RELEASE_MAX));
+        p.envelope_invert.set(if self.envelope_invert {1.0} else {0.0});
+        p.offline_hq.set(if self.offline_hq {1.0} else {0.0});
+        p.ceiling.set(self.ceiling.clamp(CEILING_MIN, CEILING_MAX));
+        p.cv_depth.set(self.cv_depth.clamp(0.0, CV_DEPTH_MAX));
+        p.send_mode.set(if self.send_mode {1.0} else {0.0});
+        p.spectral_gate.set(if self.spectral_gate {1.0} else {0.0});
+        p.gate_threshold.set(self.gate_threshold.clamp(GATE_THRESHOLD_MIN, GATE_THRESHOLD_MAX));
+        p.gate_smoothing.set(self.gate_smoothing.clamp(0.0, GATE_SMOOTHING_MAX));
+        p.pre_delay.set(self.pre_delay.clamp(0.0, PRE_DELAY_MAX));
+        p.drive.set(self.drive.clamp(0.0, DRIVE_MAX));
+        p.saturation_placement.set(enum_value(self.saturation_post as usize, 2));
+        p.haas.set(self.haas.clamp(0.0, HAAS_MAX));
+        p.resynthesis_only.set(if self.resynthesis_only {1.0} else {0.0});
+        p.midi_channel.set(enum_value(self.midi_channel, MIDI_CHANNEL_NAMES.len()));
+        p.hop.set(enum_value(self.hop.max(1).ilog2() as usize, 4));
+        p.low_cut.set(self.low_cut.clamp(LOW_CUT_MIN, LOW_CUT_MAX));
+        p.fine_link.set(enum_value(self.fine_absolute as usize, 2));
+        p.stereo_mode.set(enum_value(self.stereo_mode as usize, StereoMode::VARIANT_COUNT));
+        p.channel_link.set(if self.channel_link {1.0} else {0.0});
+        p.noise_gate.set(if self.noise_gate {1.0} else {0.0});
+        p.noise_gate_threshold.set(self.noise_gate_threshold.clamp(NOISE_GATE_THRESHOLD_MIN, NOISE_GATE_THRESHOLD_MAX));
+        p.noise_gate_hysteresis.set(self.noise_gate_hysteresis.clamp(0.0, NOISE_GATE_HYSTERESIS_MAX));
+        p.dc_bin.set(enum_value(self.dc_bin as usize, DcBin::VARIANT_COUNT));
+        p.cpu_guard.set(if self.cpu_guard {1.0} else {0.0});
+        p.trim_left.set(self.trim_left.clamp(TRIM_MIN, TRIM_MAX));
+        p.trim_right.set(self.trim_right.clamp(TRIM_MIN, TRIM_MAX));
+        p.invert_wet_left.set(if self.invert_wet_left {1.0} else {0.0});
+        p.invert_wet_right.set(if self.invert_wet_right {1.0} else {0.0});
+        p.wow_depth.set(self.wow_depth.clamp(0.0, WOW_DEPTH_MAX));
+        p.wow_rate.set(self.wow_rate.clamp(WOW_RATE_MIN, WOW_RATE_MAX));
+        p.flutter_depth.set(self.flutter_depth.clamp(0.0, FLUTTER_DEPTH_MAX));
+        p.flutter_rate.set(self.flutter_rate.clamp(FLUTTER_RATE_MIN, FLUTTER_RATE_MAX));
+        p.sidechain_gate.set(if self.sidechain_gate {1.0} else {0.0});
+        p.sidechain_threshold.set(self.sidechain_threshold.clamp(NOISE_GATE_THRESHOLD_MIN, NOISE_GATE_THRESHOLD_MAX));
+        p.sidechain_attack.set(self.sidechain_attack.clamp(ENVELOPE_ATTACK_MIN, ENVELOPE_ATTACK_MAX));
+        p.sidechain_release.set(self.sidechain_release.clamp(ENVELOPE_RELEASE_MIN, ENVELOPE_RELEASE_MAX));
+        p.cutoff_glide.set(self.cutoff_glide.clamp(CUTOFF_GLIDE_MIN, CUTOFF_GLIDE_MAX));
+        p.pitch_limit.set(if self.pitch_limit {1.0} else {0.0});
+        p.hann_window.set(if self.hann_window {1.0} else {0.0});
+        p.internal_rate.set(enum_value(self.internal_rate as usize, InternalRate::VARIANT_COUNT));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::Ordering;
+
+    use vst::prelude::PluginParameters;
+
+    use super::{BasicFilterParameters, Control, Params, Snapshot, NO_CC, ROUTING_IDENTITY};
+
+    /// Sets every control, the routing and a CC binding to something other than their defaults.
+    fn scramble(p: &BasicFilterParameters)
+    {
+        for v in Control::VARIANTS
+        {
+            p.set_parameter(v as i32, (v as usize*37%101) as f32/100.0);
+        }
+        p.set_routing([[0.0, 1.0], [1.0, 0.0]], [[0.5, 0.5], [0.5, 0.5]]);
+        p.midi_cc[Control::Mix as usize].store(7, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn reset_to_defaults_restores_every_control()
+    {
+        let p = BasicFilterParameters::default();
+        let defaults = BasicFilterParameters::default();
+        scramble(&p);
+        let touched = p.touched.load(Ordering::Relaxed);
+
+        p.reset_to_defaults();
+        for v in Control::VARIANTS
+        {
+            let (value, default) = (p.get_parameter(v as i32), defaults.get_parameter(v as i32));
+            assert!((value - default).abs() < 1e-6, "{} is at {} instead of {}", v.key(), value, default);
+        }
+        assert_eq!(p.routing(), [ROUTING_IDENTITY; 2]);
+        assert!(p.midi_cc.iter().all(|midi_cc| midi_cc.load(Ordering::Relaxed) == NO_CC));
+        assert_eq!(p.touched.load(Ordering::Relaxed), touched);
+    }
+
+    #[test]
+    fn restore_brings_back_the_snapshot()
+    {
+        let p = BasicFilterParameters::default();
+        let snapshot = p.snapshot();
+        scramble(&p);
+        let touched = p.touched.load(Ordering::Relaxed);
+
+        p.restore(&snapshot);
+        assert!(p.snapshot() == snapshot, "restored to something other than the snapshot");
+        assert_eq!(p.touched.load(Ordering::Relaxed), touched);
+    }
+
+    #[test]
+    fn parameter_text_follows_the_precision()
+    {
+        let p = BasicFilterParameters::default();
+        p.pitch.set(7.0/12.0);
+        // 1.23456 cents
+        p.pitch_fine.set(0.0123456);
+        p.mix.set(0.257);
+        p.wet_gain.set(1.5);
+        assert_eq!(p.get_parameter_text(Control::Pitch as i32), "7");
+        let fine = p.get_parameter_text(Control::PitchFine as i32);
+        assert_eq!(fine.split('.').nth(1).map(str::len), Some(Control::PitchFine.precision()));
+        assert!(fine.starts_with("1.23456"), "{fine}");
+        assert_eq!(p.get_parameter_text(Control::Mix as i32), "26");
+        assert_eq!(p.get_parameter_text(Control::WetGain as i32), "150");
+    }
+
+    #[test]
+    fn midi_learn_binds_the_last_touched_control()
+    {
+        let p = BasicFilterParameters::default();
+        p.set_parameter(Control::Mix as i32, 0.3);
+        p.set_parameter(Control::MidiLearn as i32, 1.0);
+        assert!(p.learn_cc(21));
+        assert!(p.midi_learn.get() < 0.5);
+        // Disarmed, the next CC moves the control instead of binding
+        assert!(!p.learn_cc(22));
+        p.apply_cc(21, 127);
+        assert_eq!(p.mix.get(), 1.0);
+        p.apply_cc(22, 0);
+        assert_eq!(p.mix.get(), 1.0);
+    }
+
+    #[test]
+    fn params_round_trip()
+    {
+        let p = BasicFilterParameters::default();
+        scramble(&p);
+        let params = Params::from_atomic(&p);
+        let q = BasicFilterParameters::default();
+        params.apply(&q);
+        assert!(Params::from_atomic(&q) == params);
+    }
+
+    #[test]
+    fn params_are_clamped_to_the_range()
+    {
+        let p = BasicFilterParameters::default();
+        let mut params = Params::from_atomic(&p);
+        params.pitch = 100.0;
+        params.mix = -1.0;
+        params.wet_gain = 10.0;
+        params.pre_delay = 1e6;
+        params.wow_rate = -5.0;
+        params.apply(&p);
+        for v in Control::VARIANTS
+        {
+            let value = p.get_parameter(v as i32);
+            assert!((0.0..=1.0).contains(&value), "{} is at {}", v.key(), value);
+        }
+        assert_eq!(p.get_parameter(Control::Pitch as i32), 1.0);
+        assert_eq!(p.get_parameter(Control::Mix as i32), 0.0);
+        assert_eq!(p.get_parameter(Control::WetGain as i32), 1.0);
+        assert_eq!(p.get_parameter(Control::PreDelay as i32), 1.0);
+        assert_eq!(p.get_parameter(Control::WowRate as i32), 0.0);
+    }
+
+    #[test]
+    fn snapshots_hash_by_the_values()
+    {
+        let hash = |snapshot: &Snapshot| {
+            let mut hasher = DefaultHasher::new();
+            snapshot.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (p, q) = (BasicFilterParameters::default(), BasicFilterParameters::default());
+        scramble(&p);
+        scramble(&q);
+        assert!(p.snapshot() == q.snapshot());
+        assert_eq!(hash(&p.snapshot()), hash(&q.snapshot()));
+
+        q.set_parameter(Control::Mix as i32, 0.123);
+        assert!(p.snapshot() != q.snapshot());
+        assert_ne!(hash(&p.snapshot()), hash(&q.snapshot()));
+
+        // Same value, whatever the sign of zero
+        let mut values = p.snapshot().values();
+        values[Control::Mix as usize] = 0.0;
+        let zero = Snapshot::new(values);
+        values[Control::Mix as usize] = -0.0;
+        assert!(Snapshot::new(values) == zero);
+        assert_eq!(hash(&Snapshot::new(values)), hash(&zero));
+    }
+}
@@ -0,0 +1,1125 @@
+use std::f64::consts::{TAU, PI};
+
+use num::{Complex, Zero};
+use real_time_fir_iir_filters::{iir::{first::FirstOrderFilter, third::ThirdOrderButterworthFilter}, Filter};
+use signal_processing::Sdft;
+
+use crate::{anti_alias::AntiAlias, crossover::BandMeter, error::PitchShifterError, fir::FirFilter, processor::{AudioProcessor, DcBin, Settings}, CHANNEL_COUNT, F_ANTI_POP, WINDOW_LENGTH};
+
+const MARGIN: f64 = 0.2;
+
+/// Narrowest the anti-alias band is allowed to get, in octaves, so extreme shifts never silence the wet completely.
+const MIN_PASSBAND: f64 = 1.0;
+
+/// Length of the FIR band-limiting filters in linear-phase mode.
+const FIR_LENGTH: usize = 255;
+
+/// Largest resynthesis hop the shifter is expected to run at, see [`Settings::hop`].
+pub const HOP_MAX: usize = 8;
+
+/// Latency of the shifter with the settings that delay it the most.
+pub const MAX_LATENCY: usize = WINDOW_LENGTH - 1 + FIR_LENGTH - 1 + HOP_MAX - 1;
+
+/// Low cut of the anti-alias floors in bins of the analysis window, when [`Settings::low_cut`] is left at zero.
+pub const LOW_CUT_DEFAULT: f64 = 8.0;
+
+/// Frequencies in Hz between the low, mid and high bands of [`spectral_balance`](AudioProcessor::spectral_balance).
+pub const BALANCE_CROSSOVERS: [f64; 2] = [250.0, 4000.0];
+
+/// Length of the windowed sinc reading the resynthesis between whole samples, see [`PitchShifter::interpolate`].
+const INTERPOLATION_TAPS: usize = 16;
+
+/// Cutoff of the optional DC blocker on the output.
+const F_DC_BLOCK: f64 = 5.0;
+
+/// Largest gain the normalization may apply, so it doesn't blow up the noise floor when the resynthesis goes quiet.
+const NORMALIZE_GAIN_MAX: f64 = 4.0;
+
+/// Time constant in seconds for the anti-alias cutoffs to follow a pitch change, so large jumps don't click or upset the filters.
+/// Used when [`Settings::cutoff_glide`] is left at zero.
+pub const CUTOFF_GLIDE_TIME: f64 = 0.01;
+
+/// Time constant in seconds for the phase to settle back to zero at unity pitch.
+const OMEGA_SETTLE_TIME: f64 = 0.05;
+
+/// Angular cutoffs of the anti-alias band for a given pitch ratio, as `[ceil0, ceil1, floor0, floor1]`.
+///
+/// `ceil0` and `floor0` band-limit the input before analysis, `ceil1` and `floor1` band-limit the resynthesized output.
+/// The ceilings stay `MARGIN` octaves short of where the shift would take anything past Nyquist, and of Nyquist itself.
+/// The floors sit `low_cut` bins of the analysis window up, where the window is too short to resolve anything cleanly.
+/// Each floor is kept at least `MIN_PASSBAND` octaves below its ceiling.
+pub fn anti_alias_cutoffs(rate: f64, pitch_mul: f64, low_cut: f64) -> [f64; 4]
+{
+    // Never right at Nyquist, even on the side the shift doesn't push past it
+    let omega_ceil0 = rate/pitch_mul.max(1.0)*2.0f64.powf(-MARGIN)*PI;
+    let omega_ceil1 = rate*pitch_mul.min(1.0)*2.0f64.powf(-MARGIN)*PI;
+    let omega_floor0 = rate/pitch_mul*low_cut/WINDOW_LENGTH as f64*TAU*2.0f64.powf(MARGIN);
+    let omega_floor1 = rate*low_cut/WINDOW_LENGTH as f64*TAU*2.0f64.powf(MARGIN);
+
+    // At extreme downward shifts the floor climbs past the ceiling, so give up on the floor first
+    let omega_floor0 = omega_floor0.min(omega_ceil0*2.0f64.powf(-MIN_PASSBAND));
+    let omega_floor1 = omega_floor1.min(omega_ceil1*2.0f64.powf(-MIN_PASSBAND));
+    [omega_ceil0, omega_ceil1, omega_floor0, omega_floor1]
+}
+
+/// Lowest pitch ratio where the output floor from [`anti_alias_cutoffs`] still sits where `low_cut` puts it. Any lower and the floor
+/// gets pushed down by the ceiling, leaving only `MIN_PASSBAND` octaves of low end in the wet.
+///
+/// Shifting up never gets there, since the input floor and ceiling come down together.
+pub fn min_pitch_ratio(low_cut: f64) -> f64
+{
+    2.0*low_cut/WINDOW_LENGTH as f64*2.0f64.powf(2.0*MARGIN + MIN_PASSBAND)
+}
+
+/// Pitch shifter resynthesizing a sliding DFT of the input with a rotating phase.
+///
+/// `C` is the number of channels, fixed at build time. It defaults to stereo, like the plugin.
+pub struct PitchShifter<const C: usize = CHANNEL_COUNT>
+{
+    /// Band-limiting on each channel's input and output.
+    anti_alias: [[AntiAlias; 2]; C],
+    anti_pop_filter: [ThirdOrderButterworthFilter<f64>; C],
+    linear_phase_filter: [[FirFilter<FIR_LENGTH>; 2]; C],
+    dc_blocker: [FirstOrderFilter<f64>; C],
+    /// Anti-alias cutoffs each channel's filters are at, gliding toward `cutoffs_target`. Same order as [`anti_alias_cutoffs`].
+    cutoffs: [[f64; 4]; C],
+    cutoffs_target: [f64; 4],
+    dft: [([Complex<f64>; WINDOW_LENGTH], Vec<f64>); C],
+    /// What the spectral gate closes off of the first half of each channel's spectrum, Hann windowed if [`Settings::hann`] is on.
+    closed: [[Complex<f64>; WINDOW_LENGTH]; C],
+    /// How far open the spectral gate is on each bin resynthesized.
+    gate: [[f64; WINDOW_LENGTH/2 + 1]; C],
+    omega: [f64; C],
+    /// Samples into the current hop, and the resynthesis at the start of the last hop and this one.
+    hop: [(usize, f64, f64); C],
+    /// Smoothed power of the analysed input and of the resynthesis, for normalization.
+    energy: [[f64; 2]; C],
+    /// Meters on the analysed input and the wet coming out, for the spectral balance.
+    balance: [[BandMeter; 2]; C],
+    domega_dt: f64,
+    pitch_mul: f64,
+    /// Pitch ratio of the modulation on top of `pitch_mul`.
+    modulation_mul: f64,
+    settings: Settings,
+    rate: f64
+}
+
+impl<const C: usize> PitchShifter<C>
+{
+    pub fn new(rate: f64) -> Self
+    {
+        let mut shifter = PitchShifter {
+            anti_alias: [[AntiAlias::new(); 2]; C],
+            anti_pop_filter: [(); C].map(|()| ThirdOrderButterworthFilter::new(F_ANTI_POP*TAU)),
+            linear_phase_filter: [(); C].map(|()| [(); 2].map(|()| FirFilter::new([0.0; FIR_LENGTH]))),
+            dc_blocker: [(); C].map(|()| FirstOrderFilter::new(F_DC_BLOCK*TAU)),
+            cutoffs: [[rate*PI; 4]; C],
+            cutoffs_target: [rate*PI; 4],
+            // The SDFT only ever keeps one window of history, so this is all it will need
+            dft: [(); C].map(|()| ([Complex::zero(); WINDOW_LENGTH], Vec::with_capacity(WINDOW_LENGTH))),
+            closed: [[Complex::zero(); WINDOW_LENGTH]; C],
+            gate: [[1.0; WINDOW_LENGTH/2 + 1]; C],
+            omega: [0.0; C],
+            hop: [(0, 0.0, 0.0); C],
+            energy: [[0.0; 2]; C],
+            balance: [[BandMeter::new(BALANCE_CROSSOVERS); 2]; C],
+            domega_dt: 0.0,
+            pitch_mul: 1.0,
+            modulation_mul: 1.0,
+            settings: Settings::default(),
+            rate
+        };
+        shifter.retune();
+        shifter.snap_cutoffs();
+        shifter
+    }
+
+    /// Like [`new`](PitchShifter::new), but fails on a sample rate the shifter can't run at.
+    pub fn try_new(rate: f64) -> Result<Self, PitchShifterError>
+    {
+        if !(rate.is_finite() && rate > 0.0)
+        {
+            return Err(PitchShifterError::InvalidSampleRate(rate))
+        }
+        Ok(Self::new(rate))
+    }
+
+    /// Like [`process_block`](AudioProcessor::process_block), but fails instead of panicking on a channel that doesn't exist.
+    pub fn try_process_block(&mut self, rate: f64, channel: usize, block: &mut [f64]) -> Result<(), PitchShifterError>
+    {
+        if channel >= C
+        {
+            return Err(PitchShifterError::ChannelOutOfRange {channel, channel_count: C})
+        }
+        if !(rate.is_finite() && rate > 0.0)
+        {
+            return Err(PitchShifterError::InvalidSampleRate(rate))
+        }
+        self.process_block(rate, channel, block);
+        Ok(())
+    }
+
+    /// Processes interleaved frames of `channels` samples each in place, like `[L, R, L, R, ...]`.
+    ///
+    /// The channels don't share any state, so this comes out the same as processing each channel as a block. Fails on more channels
+    /// than the shifter was built for, before processing anything. A trailing partial frame is left as it is.
+    pub fn process_interleaved(&mut self, rate: f64, data: &mut [f64], channels: usize) -> Result<(), PitchShifterError>
+    {
+        if channels > C
+        {
+            return Err(PitchShifterError::ChannelOutOfRange {channel: channels - 1, channel_count: C})
+        }
+        if !(rate.is_finite() && rate > 0.0)
+        {
+            return Err(PitchShifterError::InvalidSampleRate(rate))
+        }
+        if channels == 0
+        {
+            return Ok(())
+        }
+        for frame in data.chunks_exact_mut(channels)
+        {
+            for (channel, x) in frame.iter_mut()
+                .enumerate()
+            {
+                *x = self.process_sample(rate, channel, *x);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rate(&self) -> f64
+    {
+        self.rate
+    }
+
+    /// Spacing of the analysis bins in Hz. Pitches closer than this blur together in the resynthesis.
+    pub fn frequency_resolution_hz(&self) -> f64
+    {
+        self.rate/WINDOW_LENGTH as f64
+    }
+
+    /// [`latency`](AudioProcessor::latency) in milliseconds with the current settings.
+    pub fn latency_ms(&self) -> f64
+    {
+        self.latency() as f64*1000.0/self.rate
+    }
+
+    /// Phase of the resynthesis on each channel, in radians from 0 up to but not including τ. Only for debugging.
+    #[cfg(feature = "diagnostics")]
+    pub fn omega(&self) -> [f64; C]
+    {
+        self.omega
+    }
+
+    /// Measures the gain of the anti-alias chains at each of the `frequencies` in Hz, as `[input, output]`, by running a sine
+    /// through them at the current pitch and settings. The output chain is measured without the anti-pop filter after it, except
+    /// in linear-phase mode where it's part of the same FIR. Leaves the shifter [`reset`](AudioProcessor::reset). Only for debugging.
+    #[cfg(feature = "diagnostics")]
+    pub fn anti_alias_response(&mut self, frequencies: &[f64]) -> Vec<[f64; 2]>
+    {
+        let response = frequencies.iter()
+            .map(|&frequency| {
+                self.reset();
+                self.snap_cutoffs();
+                // Long enough for the filters to settle, then a few periods to find the peak in
+                let period = (self.rate/frequency).ceil() as usize;
+                let settle = FIR_LENGTH + (self.rate*0.1) as usize;
+                let measure = (4*period).max(WINDOW_LENGTH);
+                let mut peak = [0.0f64; 2];
+                for n in 0..settle + measure
+                {
+                    let x = (TAU*frequency*n as f64/self.rate).sin();
+                    let y = [self.band_limit_input(0, x), self.anti_alias_output(0, x)];
+                    if n >= settle
+                    {
+                        for (peak, y) in peak.iter_mut()
+                            .zip(y)
+                        {
+                            *peak = peak.max(y.abs());
+                        }
+                    }
+                }
+                peak
+            }).collect();
+        self.reset();
+        response
+    }
+
+    /// The pitch ratio the shifter is running at, with any [modulation](AudioProcessor::set_pitch_modulation) on top. Pitch
+    /// changes take effect immediately, so there's no glide to lag behind.
+    pub fn current_pitch_ratio(&self) -> f64
+    {
+        self.pitch_mul*self.modulation_mul
+    }
+
+    /// Puts every channel's resynthesis phase back to zero, so segments rendered from the same point come out the same.
+    ///
+    /// Unlike [`reset`](AudioProcessor::reset), the SDFT history, the filters and the settings are left alone, so the output
+    /// carries on from what was already analysed rather than from silence.
+    pub fn reset_phase(&mut self)
+    {
+        self.omega = [0.0; C];
+        // Resynthesize on the next sample, at the new phase
+        for (hop_count, _, _) in self.hop.iter_mut()
+        {
+            *hop_count = 0;
+        }
+    }
+
+    /// Sets a channel's anti-alias filters to its current cutoffs.
+    fn apply_cutoffs(&mut self, channel: usize)
+    {
+        let [omega_ceil0, omega_ceil1, omega_floor0, omega_floor1] = self.cutoffs[channel];
+        let [anti_alias0, anti_alias1] = &mut self.anti_alias[channel];
+        anti_alias0.set_cutoffs(self.rate, omega_ceil0, omega_floor0);
+        anti_alias1.set_cutoffs(self.rate, omega_ceil1, omega_floor1);
+    }
+
+    /// Jumps every channel's cutoffs straight to the target, for when there's nothing to glide from.
+    fn snap_cutoffs(&mut self)
+    {
+        for channel in 0..C
+        {
+            self.cutoffs[channel] = self.cutoffs_target;
+            self.apply_cutoffs(channel);
+        }
+    }
+
+    /// Moves a channel's cutoffs one sample closer to the target.
+    fn glide_cutoffs(&mut self, channel: usize)
+    {
+        let cutoffs = &mut self.cutoffs[channel];
+        if *cutoffs == self.cutoffs_target
+        {
+            return
+        }
+        let glide = if self.settings.cutoff_glide > 0.0 {self.settings.cutoff_glide} else {CUTOFF_GLIDE_TIME};
+        let alpha = (1.0/(glide*self.rate)).min(1.0);
+        for (cutoff, &target) in cutoffs.iter_mut()
+            .zip(self.cutoffs_target.iter())
+        {
+            *cutoff += (target - *cutoff)*alpha;
+            if (target - *cutoff).abs() <= target*1e-6
+            {
+                *cutoff = target;
+            }
+        }
+        self.apply_cutoffs(channel);
+    }
+
+    fn ifft_once<const N: usize>(omega: f64, x_f: &[Complex<f64>; N], dc_bin: DcBin) -> f64
+    {
+        let z = Complex::cis(omega);
+        let mut z_n = z;
+        (Self::dc(z, x_f[0], dc_bin) + x_f[1..N/2 + 1].iter()
+            .enumerate()
+            .map(|(i, x_f)| {
+                let y = x_f*z_n;
+                z_n *= z;
+                y.re*Self::conjugate_weight::<N>(i + 1)
+            }).sum::<f64>())/N as f64
+    }
+
+    /// Like [`ifft_once`](PitchShifter::ifft_once), but reads between the phases that land on whole samples of the window with a
+    /// short windowed sinc, instead of the whole window's periodic sinc. With `hann` it reads the Hann windowed input, and `dc` is
+    /// the DC bin of the spectrum it's read from.
+    ///
+    /// At those phases the resynthesis is just the windowed input, so this is exact there. In between, the periodic sinc rings off
+    /// the jump where the window wraps around, which as the phase crawls along at a small detune is heard as a slow beating.
+    fn interpolate<const N: usize>(omega: f64, dc: Complex<f64>, history: &[f64], hann: bool, dc_bin: DcBin) -> f64
+    {
+        // Samples from the oldest one in the window, which the history keeps last
+        let position = omega*N as f64/TAU;
+        let m = position.floor();
+        let t = position - m;
+        let x = |m: isize| history.get(N - 1 - m.rem_euclid(N as isize) as usize)
+            .copied()
+            .unwrap_or(0.0)*if hann {0.5 - 0.5*(TAU*m as f64/N as f64).cos()} else {1.0};
+        let (mut y, mut sum) = (0.0, 0.0);
+        for j in 1 - INTERPOLATION_TAPS as isize/2..=INTERPOLATION_TAPS as isize/2
+        {
+            let u = j as f64 - t;
+            let window = 0.5 + 0.5*(TAU*u/(INTERPOLATION_TAPS + 1) as f64).cos();
+            let h = if u == 0.0 {1.0} else {(PI*u).sin()/(PI*u)}*window;
+            y += x(m as isize + j)*h;
+            sum += h;
+        }
+        // The DC bin was read back as it is, so swap it for what the setting wants
+        y/sum + (Self::dc(Complex::cis(omega), dc, dc_bin) - dc.re)/N as f64
+    }
+
+    /// The DC bin's part of the resynthesis, with `z` the rotation of the first bin.
+    fn dc(z: Complex<f64>, x_f: Complex<f64>, dc_bin: DcBin) -> f64
+    {
+        match dc_bin
+        {
+            DcBin::Pass => x_f.re,
+            DcBin::Rotate => (x_f*z).re,
+            DcBin::Remove => 0.0
+        }
+    }
+
+    /// Bins count twice in the real inverse DFT, once for themselves and once for their conjugate. Except the Nyquist bin of an even
+    /// length window, which is its own conjugate.
+    fn conjugate_weight<const N: usize>(k: usize) -> f64
+    {
+        if 2*k == N {1.0} else {2.0}
+    }
+
+    /// Scales each bin of the first half of a spectrum by how far its gate is closed, leaving what the gate takes out. Moves the
+    /// gates toward open for bins at or above `threshold` first.
+    fn close_gates<const N: usize>(x_f: &mut [Complex<f64>; N], gate: &mut [f64], threshold: f64, alpha: f64)
+    {
+        for (x_f, gate) in x_f[..N/2 + 1].iter_mut()
+            .zip(gate.iter_mut())
+        {
+            *x_f *= 1.0 - Self::open_gate(gate, *x_f, threshold, alpha);
+        }
+    }
+
+    /// Moves a bin's gate toward open if the bin is at or above `threshold`, and toward closed otherwise, returning how far open it is.
+    fn open_gate(gate: &mut f64, x_f: Complex<f64>, threshold: f64, alpha: f64) -> f64
+    {
+        let target = if x_f.norm() >= threshold {1.0} else {0.0};
+        *gate += (target - *gate)*alpha;
+        *gate
+    }
+
+    /// Hann windows the first half of a spectrum into `windowed`, by convolving it with the window's three bin kernel.
+    fn hann<const N: usize>(x_f: &[Complex<f64>; N], windowed: &mut [Complex<f64>; N])
+    {
+        for k in 0..N/2 + 1
+        {
+            // The neighbours past either end of the half are mirrored conjugates
+            let below = if k == 0 {x_f[1].conj()} else {x_f[k - 1]};
+            let above = if k == N/2 {x_f[N/2 - 1].conj()} else {x_f[k + 1]};
+            windowed[k] = x_f[k]*0.5 - (below + above)*0.25;
+        }
+    }
+
+    /// Band-limits a sample into a channel's analysis, returning what went in.
+    fn analyse(&mut self, rate: f64, channel: usize, x: f64) -> f64
+    {
+        if rate != self.rate
+        {
+            self.set_sample_rate(rate);
+            self.reset();
+        }
+
+        // The FIR coefficients are swapped in one go, only the IIR cutoffs glide
+        if !self.settings.linear_phase
+        {
+            self.glide_cutoffs(channel);
+        }
+
+        let z = self.band_limit_input(channel, x);
+        let dft = &mut self.dft[channel];
+        let capacity = dft.1.capacity();
+        dft.0.sdft(&mut [z], &mut dft.1);
+        debug_assert_eq!(dft.1.capacity(), capacity, "SDFT history reallocated on the audio thread");
+        z
+    }
+
+    /// The anti-alias band-limiting in front of the analysis.
+    fn band_limit_input(&mut self, channel: usize, x: f64) -> f64
+    {
+        let [anti_alias0, _] = &mut self.anti_alias[channel];
+        let [linear_phase_filter0, _] = &mut self.linear_phase_filter[channel];
+
+        if self.settings.linear_phase
+        {
+            linear_phase_filter0.filter(x)
+        }
+        else
+        {
+            anti_alias0.filter(self.settings.aliasing, x)
+        }
+    }
+
+    /// The anti-alias band-limiting after the resynthesis. In linear-phase mode the FIR does the anti-pop filtering too.
+    fn anti_alias_output(&mut self, channel: usize, y: f64) -> f64
+    {
+        let [_, anti_alias1] = &mut self.anti_alias[channel];
+        let [_, linear_phase_filter1] = &mut self.linear_phase_filter[channel];
+
+        if self.settings.linear_phase
+        {
+            linear_phase_filter1.filter(y)
+        }
+        else
+        {
+            anti_alias1.filter(self.settings.aliasing, y)
+        }
+    }
+
+    /// The anti-alias band-limiting and anti-pop filtering after the resynthesis.
+    fn band_limit_output(&mut self, channel: usize, y: f64) -> f64
+    {
+        let y = self.anti_alias_output(channel, y);
+        if self.settings.linear_phase
+        {
+            y
+        }
+        else
+        {
+            let [y, _, _, _] = self.anti_pop_filter[channel].filter(self.rate, y);
+            y
+        }
+    }
+
+    /// Meters a channel's analysed input `z` and wet `y` for the spectral balance, over about a window.
+    fn meter_balance(&mut self, channel: usize, z: f64, y: f64)
+    {
+        let [input, wet] = &mut self.balance[channel];
+        let alpha = 1.0/WINDOW_LENGTH as f64;
+        input.push(self.rate, z, alpha);
+        wet.push(self.rate, y, alpha);
+    }
+
+    /// Steps a channel's phase on by one sample.
+    fn advance_phase(&mut self, channel: usize)
+    {
+        let omega = &mut self.omega[channel];
+        if self.domega_dt == 0.0
+        {
+            // At unity pitch a leftover phase is a fixed all-pass that combs with the dry, so rotate back to zero along the shortest path
+            let phase = if *omega > PI {*omega - TAU} else {*omega};
+            *omega = (phase*(1.0 - 1.0/(OMEGA_SETTLE_TIME*self.rate)) + TAU) % TAU;
+        }
+        else
+        {
+            *omega = (*omega + self.domega_dt + TAU) % TAU;
+        }
+    }
+
+    fn retune(&mut self)
+    {
+        let low_cut = if self.settings.low_cut > 0.0 {self.settings.low_cut} else {LOW_CUT_DEFAULT};
+        self.cutoffs_target = anti_alias_cutoffs(self.rate, self.pitch_mul, low_cut);
+        let [omega_ceil0, omega_ceil1, omega_floor0, omega_floor1] = self.cutoffs_target;
+
+        if self.settings.linear_phase
+        {
+            // The FIR output band also takes over for the anti-pop filter
+            let b0 = FirFilter::<FIR_LENGTH>::band_pass(self.rate, omega_floor0, omega_ceil0);
+            let b1 = FirFilter::<FIR_LENGTH>::band_pass(self.rate, omega_floor1, omega_ceil1.min(F_ANTI_POP*TAU));
+            for [filter0, filter1] in self.linear_phase_filter.iter_mut()
+            {
+                filter0.b = b0;
+                filter1.b = b1;
+            }
+        }
+    }
+}
+
+impl<const C: usize> AudioProcessor for PitchShifter<C>
+{
+    fn set_sample_rate(&mut self, rate: f64)
+    {
+        self.rate = rate;
+        self.retune();
+        self.snap_cutoffs();
+    }
+
+    fn set_block_size(&mut self, _size: usize)
+    {
+        // The SDFT history holds one window regardless of block size, so just make sure it's allocated up front
+        for dft in self.dft.iter_mut()
+        {
+            dft.1.reserve_exact(WINDOW_LENGTH.saturating_sub(dft.1.len()));
+        }
+    }
+
+    fn configure(&mut self, settings: &Settings)
+    {
+        if *settings != self.settings
+        {
+            self.settings = *settings;
+            self.retune();
+        }
+    }
+
+    fn latency(&self) -> usize
+    {
+        // The resynthesis at zero phase reconstructs the oldest sample in the window, which the Hann window leaves out in favour of
+        // the middle one
+        let latency = if self.settings.hann {WINDOW_LENGTH/2 - 1} else {WINDOW_LENGTH - 1} + self.settings.hop.clamp(1, HOP_MAX) - 1;
+        if self.settings.linear_phase {latency + FIR_LENGTH - 1} else {latency}
+    }
+
+    fn set_pitch(&mut self, octaves: f64)
+    {
+        let pitch_mul = 2.0f64.powf(octaves);
+        self.domega_dt = TAU*(pitch_mul*self.modulation_mul - 1.0)/WINDOW_LENGTH as f64;
+
+        if pitch_mul != self.pitch_mul
+        {
+            self.pitch_mul = pitch_mul;
+            self.retune();
+        }
+    }
+
+    fn set_pitch_modulation(&mut self, octaves: f64)
+    {
+        self.modulation_mul = 2.0f64.powf(octaves);
+        self.domega_dt = TAU*(self.pitch_mul*self.modulation_mul - 1.0)/WINDOW_LENGTH as f64;
+    }
+
+    fn tail_size(&self) -> usize
+    {
+        WINDOW_LENGTH
+    }
+
+    fn reset(&mut self)
+    {
+        for anti_alias in self.anti_alias.iter_mut()
+            .flatten()
+        {
+            anti_alias.reset();
+        }
+        for filter in self.anti_pop_filter.iter_mut()
+        {
+            *filter = ThirdOrderButterworthFilter::new(filter.omega);
+        }
+        for filter in self.linear_phase_filter.iter_mut()
+            .flatten()
+        {
+            filter.reset();
+        }
+        self.dc_blocker = [(); C].map(|()| FirstOrderFilter::new(F_DC_BLOCK*TAU));
+        for dft in self.dft.iter_mut()
+        {
+            dft.0 = [Complex::zero(); WINDOW_LENGTH];
+            dft.1.clear();
+        }
+        self.gate = [[1.0; WINDOW_LENGTH/2 + 1]; C];
+        self.omega = [0.0; C];
+        self.hop = [(0, 0.0, 0.0); C];
+        self.energy = [[0.0; 2]; C];
+        for meter in self.balance.iter_mut()
+            .flatten()
+        {
+            meter.reset();
+        }
+    }
+
+    fn link_channels(&mut self)
+    {
+        if let Some(&omega) = self.omega.first()
+        {
+            self.omega = [omega; C];
+        }
+    }
+
+    fn spectral_balance(&self, channel: usize) -> Option<[[f64; 3]; 2]>
+    {
+        self.balance.get(channel)
+            .map(|meters| meters.each_ref().map(BandMeter::energy))
+    }
+
+    fn analyse_sample(&mut self, rate: f64, channel: usize, x: f64)
+    {
+        let z = self.analyse(rate, channel, x);
+        self.meter_balance(channel, z, 0.0);
+        self.advance_phase(channel);
+        // Start the next resynthesis fresh, rather than ramping from whatever was last heard
+        self.hop[channel] = (0, 0.0, 0.0);
+    }
+
+    fn process_sample(&mut self, rate: f64, channel: usize, x: f64) -> f64
+    {
+        let z = self.analyse(rate, channel, x);
+
+        let dft = &self.dft[channel];
+        let omega = self.omega[channel];
+        let [energy_in, energy_out] = &mut self.energy[channel];
+
+        let hop = self.settings.hop.clamp(1, HOP_MAX);
+        let (hop_count, hop_prev, hop_next) = &mut self.hop[channel];
+        if *hop_count == 0
+        {
+            let phase = if self.settings.resynthesis_only {0.0} else {omega};
+            let (hann, dc_bin) = (self.settings.hann, self.settings.dc_bin);
+            // Two heads half a window apart with the Hann window, where its weights add up to one, so the sweep through the window
+            // crossfades between them instead of jumping where it wraps around
+            let heads = |read: &dyn Fn(f64) -> f64| if hann {read(phase) + read(phase + PI)} else {read(phase)};
+            // The DC bin of the Hann windowed spectrum, from its three bin kernel
+            let dc = if hann {dft.0[0]*0.5 - dft.0[1].re*0.5} else {dft.0[0]};
+            let y = heads(&|phase| Self::interpolate::<WINDOW_LENGTH>(phase, dc, &dft.1, hann, dc_bin));
+            let y = if self.settings.gate_threshold > 0.0
+            {
+                let closed = &mut self.closed[channel];
+                if hann
+                {
+                    Self::hann(&dft.0, closed);
+                }
+                else
+                {
+                    closed[..WINDOW_LENGTH/2 + 1].copy_from_slice(&dft.0[..WINDOW_LENGTH/2 + 1]);
+                }
+                // A full scale sine peaks at N/2 in its bin, and the window halves that
+                let threshold = self.settings.gate_threshold*WINDOW_LENGTH as f64*if hann {0.25} else {0.5};
+                let alpha = (hop as f64/(self.settings.gate_smoothing*self.rate)).min(1.0);
+                Self::close_gates(closed, &mut self.gate[channel], threshold, alpha);
+                // Take out what the gate closes off, so what it leaves open is still read with the interpolation
+                let closed = &*closed;
+                y - heads(&|phase| Self::ifft_once(phase, closed, dc_bin))
+            }
+            else
+            {
+                y
+            };
+            *hop_prev = *hop_next;
+            *hop_next = y;
+        }
+        // Ramp from the last resynthesis to this one over the hop, which with a hop of 1 is just this one
+        *hop_count = (*hop_count + 1) % hop;
+        let t = if *hop_count == 0 {1.0} else {*hop_count as f64/hop as f64};
+        let y = *hop_prev + (*hop_next - *hop_prev)*t;
+        let y = if self.settings.normalize
+        {
+            // Both are averaged over about a window
+            let alpha = 1.0/WINDOW_LENGTH as f64;
+            *energy_in += (z*z - *energy_in)*alpha;
+            *energy_out += (y*y - *energy_out)*alpha;
+            if *energy_out > 0.0
+            {
+                y*(*energy_in/ *energy_out).sqrt().min(NORMALIZE_GAIN_MAX)
+            }
+            else
+            {
+                y
+            }
+        }
+        else
+        {
+            y
+        };
+        let y = self.band_limit_output(channel, y);
+        let y = if self.settings.dc_block
+        {
+            let [_, y] = self.dc_blocker[channel].filter(self.rate, y);
+            y
+        }
+        else
+        {
+            y
+        };
+
+        self.meter_balance(channel, z, y);
+        self.advance_phase(channel);
+
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::f64::consts::{PI, TAU};
+
+    use crate::{error::PitchShifterError, processor::{AudioProcessor, Settings}, test_signals, transfer_function, WINDOW_LENGTH};
+
+    use super::{anti_alias_cutoffs, PitchShifter, LOW_CUT_DEFAULT, MARGIN};
+
+    const RATE: f64 = 44100.0;
+
+    /// Peak to peak variation of the RMS level over blocks of `block` samples, relative to the mean.
+    fn modulation_depth(y: &[f64], block: usize) -> f64
+    {
+        let rms: Vec<f64> = y.chunks_exact(block)
+            .map(|y| (y.iter().map(|y| y*y).sum::<f64>()/block as f64).sqrt())
+            .collect();
+        let max = rms.iter().copied().fold(0.0, f64::max);
+        let min = rms.iter().copied().fold(f64::INFINITY, f64::min);
+        (max - min)*rms.len() as f64/rms.iter().sum::<f64>()
+    }
+
+    #[test]
+    fn detuned_tone_does_not_beat()
+    {
+        // Plain, and through a spectral gate low enough to leave the edges of the window open
+        let settings = [0.0, 3e-5].map(|gate_threshold| Settings {
+            gate_threshold,
+            gate_smoothing: 0.01,
+            ..Default::default()
+        });
+        for (settings, frequency) in settings.iter()
+            .flat_map(|settings| [2205.0, 8820.0].map(|frequency| (settings, frequency)))
+        {
+            let mut shifter = PitchShifter::<1>::new(RATE);
+            shifter.configure(settings);
+            shifter.set_pitch_cents(1.0);
+            let y: Vec<f64> = (0..2*RATE as usize).map(|n| shifter.process_sample(RATE, 0, (TAU*frequency*n as f64/RATE).sin()))
+                .collect();
+            // Blocks of whole periods, after the window has filled and the filters have settled
+            let depth = modulation_depth(&y[RATE as usize/2..], 100);
+            assert!(depth < 0.005, "{} Hz beats with a depth of {} at a gate threshold of {}", frequency, depth, settings.gate_threshold);
+        }
+    }
+
+    fn assert_close(actual: [f64; 4], expected: [f64; 4])
+    {
+        for (actual, expected) in actual.into_iter()
+            .zip(expected)
+        {
+            assert!((actual - expected).abs() <= expected*1e-12, "{:?} is not {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn anti_alias_cutoffs_follow_the_pitch()
+    {
+        let margin = 2.0f64.powf(MARGIN);
+        let floor = RATE*LOW_CUT_DEFAULT/WINDOW_LENGTH as f64*TAU*margin;
+
+        // Both bands stop short of Nyquist by the margin
+        assert_close(anti_alias_cutoffs(RATE, 1.0, LOW_CUT_DEFAULT), [RATE*PI/margin, RATE*PI/margin, floor, floor]);
+        // Going up the input has to make room under Nyquist, and its floor comes down with it
+        assert_close(anti_alias_cutoffs(RATE, 2.0, LOW_CUT_DEFAULT), [RATE*0.5*PI/margin, RATE*PI/margin, floor*0.5, floor]);
+        // Going down it's the output that gets cut, and the input floor goes up
+        assert_close(anti_alias_cutoffs(RATE, 0.5, LOW_CUT_DEFAULT), [RATE*PI/margin, RATE*0.5*PI/margin, floor*2.0, floor]);
+    }
+
+    #[test]
+    fn anti_alias_cutoffs_stay_below_nyquist()
+    {
+        for pitch_mul in [0.5, 1.0, 2.0]
+        {
+            for cutoff in anti_alias_cutoffs(RATE, pitch_mul, LOW_CUT_DEFAULT)
+            {
+                assert!(cutoff > 0.0 && cutoff < RATE*PI, "cutoff {} outside (0, {}) at a ratio of {}", cutoff, RATE*PI, pitch_mul);
+            }
+        }
+    }
+
+    #[test]
+    fn phase_reset_renders_segments_reproducibly()
+    {
+        let segment = test_signals::multi_tone(RATE, &[(440.0, 0.3), (1230.0, 0.2)], 8192);
+        let render = |reset_phase: bool| {
+            let mut shifter = PitchShifter::<1>::new(RATE);
+            shifter.set_pitch_semitones(5.0);
+            let mut y: Vec<f64> = segment.iter()
+                .map(|&x| shifter.process_sample(RATE, 0, x))
+                .collect();
+            if reset_phase
+            {
+                shifter.reset_phase();
+                assert_eq!(shifter.omega, [0.0], "phase left over after the reset");
+                assert_eq!(shifter.pitch_mul, 2.0f64.powf(5.0/12.0), "the reset touched the pitch");
+            }
+            y.extend(segment.iter()
+                .map(|&x| shifter.process_sample(RATE, 0, x))
+            );
+            y
+        };
+        let (first, second) = (render(true), render(true));
+        assert!(first == second, "renders with a phase reset between the segments differ");
+        // And the reset did something, the second segment carries on differently without it
+        let carried_on = render(false);
+        assert!(first[segment.len()..] != carried_on[segment.len()..], "the phase reset left the output as it was");
+    }
+
+    #[test]
+    fn failures_come_back_as_errors()
+    {
+        for rate in [0.0, -RATE, f64::NAN, f64::INFINITY]
+        {
+            assert!(matches!(PitchShifter::<1>::try_new(rate), Err(PitchShifterError::InvalidSampleRate(_))), "{} Hz was accepted", rate);
+        }
+        let mut shifter = PitchShifter::<2>::new(RATE);
+        let mut block = [0.0; 16];
+        assert_eq!(shifter.try_process_block(RATE, 2, &mut block), Err(PitchShifterError::ChannelOutOfRange {channel: 2, channel_count: 2}));
+        assert_eq!(shifter.try_process_block(0.0, 0, &mut block), Err(PitchShifterError::InvalidSampleRate(0.0)));
+        assert_eq!(shifter.try_process_block(RATE, 1, &mut block), Ok(()));
+    }
+
+    #[test]
+    fn changing_rate_resets()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch_ratio(2.0);
+        for n in 0..100
+        {
+            shifter.process_sample(RATE, 0, (n as f64*0.1).sin());
+        }
+
+        shifter.process_sample(48000.0, 0, 1.0);
+        assert_eq!(shifter.rate(), 48000.0);
+        // Straight to the new rate's cutoffs, rather than gliding over from the old ones
+        assert_eq!(shifter.cutoffs[0], anti_alias_cutoffs(48000.0, 2.0, LOW_CUT_DEFAULT));
+        // Nothing from before the change is left in the window
+        assert_eq!(shifter.dft[0].1.len(), 1);
+    }
+
+    #[test]
+    fn phase_settles_at_unity_pitch()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch_semitones(1.0);
+        for _ in 0..RATE as usize/3
+        {
+            shifter.process_sample(RATE, 0, 0.0);
+        }
+        assert!(shifter.omega[0] != 0.0);
+
+        shifter.set_pitch(0.0);
+        for _ in 0..RATE as usize/2
+        {
+            shifter.process_sample(RATE, 0, 0.0);
+        }
+        let omega = shifter.omega[0];
+        assert!(omega.min(TAU - omega) < 1e-3, "phase stuck at {}", omega);
+    }
+
+    /// Across the passband at unity pitch, above the low cut and below where the anti-pop cut starts.
+    const PASSBAND: [f64; 5] = [700.0, 1000.0, 2000.0, 4000.0, 6000.0];
+
+    #[test]
+    fn linear_phase_has_a_linear_phase_response()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.configure(&Settings {
+            linear_phase: true,
+            ..Default::default()
+        });
+        let impulse_response = shifter.impulse_response(RATE, 0, 4*WINDOW_LENGTH);
+        let deviation = transfer_function::deviation_from_delay(RATE, &impulse_response, shifter.latency(), &PASSBAND);
+        assert!(deviation.phase < 1e-6, "{:?}", deviation);
+    }
+
+    #[test]
+    fn unity_pitch_is_transparent()
+    {
+        for linear_phase in [false, true]
+        {
+            let mut shifter = PitchShifter::<1>::new(RATE);
+            shifter.configure(&Settings {
+                linear_phase,
+                ..Default::default()
+            });
+            let impulse_response = shifter.impulse_response(RATE, 0, 4*WINDOW_LENGTH);
+            // The minimum-phase filters shift the phase near the band edges, so only the level is held to the ideal
+            let deviation = transfer_function::deviation_from_delay(RATE, &impulse_response, shifter.latency(), &PASSBAND);
+            assert!(deviation.magnitude < 0.5, "{:?} with linear phase {}", deviation, linear_phase);
+        }
+    }
+
+    #[test]
+    fn impulse_dies_out()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch_semitones(7.0);
+        let y: Vec<f64> = test_signals::impulse(8*WINDOW_LENGTH).into_iter()
+            .map(|x| shifter.process_sample(RATE, 0, x))
+            .collect();
+        assert!(y[..WINDOW_LENGTH].iter().any(|y| y.abs() > 1e-3));
+        // Once it's left the window, only the filters ringing down are left
+        let tail = y[6*WINDOW_LENGTH..].iter().fold(0.0f64, |max, y| max.max(y.abs()));
+        assert!(tail < 1e-6, "still at {} long after the impulse", tail);
+    }
+
+    #[test]
+    fn step_settles_to_silence()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch_semitones(5.0);
+        let y: Vec<f64> = test_signals::step(RATE as usize).into_iter()
+            .map(|x| shifter.process_sample(RATE, 0, x))
+            .collect();
+        // The floors keep DC out of the analysis, so there's nothing for the shift to turn into a tone
+        let tail = y[RATE as usize/2..].iter().fold(0.0f64, |max, y| max.max(y.abs()));
+        assert!(tail < 1e-3, "a step leaves {} behind", tail);
+    }
+
+    #[test]
+    fn sweep_is_band_limited_going_up()
+    {
+        let length = 2*RATE as usize;
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch_semitones(12.0);
+        let y: Vec<f64> = test_signals::sweep(RATE, 100.0, 20000.0, 0.5, length).into_iter()
+            .map(|x| shifter.process_sample(RATE, 0, x))
+            .collect();
+        let rms = |y: &[f64]| (y.iter().map(|y| y*y).sum::<f64>()/y.len() as f64).sqrt();
+        // Sweeping through a few hundred Hz to a few kHz, and then past where an octave up would fold over Nyquist
+        let passband = rms(&y[length/5..3*length/5]);
+        let stopband = rms(&y[23*length/25..]);
+        assert!(stopband < passband*0.05, "{} past Nyquist against {} in the passband", stopband, passband);
+    }
+
+    #[test]
+    fn channels_do_not_leak_into_each_other()
+    {
+        let mut shifter = PitchShifter::<2>::new(RATE);
+        shifter.set_pitch_semitones(-4.0);
+        let mut wet = 0.0f64;
+        for x in test_signals::white_noise(1, 0.5, 2*WINDOW_LENGTH)
+        {
+            wet = wet.max(shifter.process_sample(RATE, 0, x).abs());
+            assert_eq!(shifter.process_sample(RATE, 1, 0.0), 0.0);
+        }
+        assert!(wet > 0.01);
+    }
+
+    #[test]
+    fn impulse_response_is_centred_on_the_latency()
+    {
+        for linear_phase in [false, true]
+        {
+            let mut shifter = PitchShifter::<1>::new(RATE);
+            shifter.configure(&Settings {
+                linear_phase,
+                ..Default::default()
+            });
+            let latency = shifter.latency();
+            let impulse_response = shifter.impulse_response(RATE, 0, 4*WINDOW_LENGTH);
+            let energy = |h: &[f64]| h.iter().map(|h| h*h).sum::<f64>();
+            let near = energy(&impulse_response[latency - 32..latency + 32])/energy(&impulse_response);
+            assert!(near > 0.9, "{} of the energy near the latency with linear phase {}", near, linear_phase);
+        }
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[test]
+    fn anti_alias_response_falls_3_db_at_the_cutoffs()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch_semitones(12.0);
+        let [omega_ceil0, omega_ceil1, omega_floor0, omega_floor1] = anti_alias_cutoffs(RATE, 2.0, LOW_CUT_DEFAULT);
+        // The bilinear transform squeezes each cutoff down toward Nyquist, so look for it where the transform puts it
+        let warped = |omega: f64| RATE/PI*(omega/(2.0*RATE)).atan();
+        let frequencies = [omega_ceil0, omega_floor0, omega_ceil1, omega_floor1].map(warped);
+        let response = shifter.anti_alias_response(&frequencies);
+        for (chain, edges) in [[0, 1], [2, 3]].into_iter()
+            .enumerate()
+        {
+            for edge in edges
+            {
+                let db = 20.0*response[edge][chain].log10();
+                assert!((db + 3.01).abs() < 0.2, "{} dB at {} Hz on chain {}", db, frequencies[edge], chain);
+            }
+        }
+    }
+
+    #[test]
+    fn interleaved_matches_planar()
+    {
+        let planar_input = [
+            test_signals::multi_tone(RATE, &[(330.0, 0.4)], 4096),
+            test_signals::white_noise(7, 0.3, 4096)
+        ];
+        let mut planar = PitchShifter::<2>::new(RATE);
+        planar.set_pitch_semitones(3.0);
+        let mut planar_output = planar_input.clone();
+        for (channel, block) in planar_output.iter_mut()
+            .enumerate()
+        {
+            planar.process_block(RATE, channel, block);
+        }
+
+        let mut interleaved = PitchShifter::<2>::new(RATE);
+        interleaved.set_pitch_semitones(3.0);
+        let mut data: Vec<f64> = planar_input[0].iter()
+            .zip(planar_input[1].iter())
+            .flat_map(|(&l, &r)| [l, r])
+            .collect();
+        interleaved.process_interleaved(RATE, &mut data, 2).unwrap();
+        for (n, frame) in data.chunks_exact(2)
+            .enumerate()
+        {
+            assert_eq!(frame, [planar_output[0][n], planar_output[1][n]], "frame {}", n);
+        }
+        assert!(interleaved.process_interleaved(RATE, &mut data, 3).is_err());
+    }
+
+    #[test]
+    fn pitching_up_raises_the_wet_high_band()
+    {
+        let input = test_signals::multi_tone(RATE, &[(300.0, 0.3), (1500.0, 0.2), (3000.0, 0.2)], 16384);
+        let high_share = |semitones: f64| {
+            let mut shifter = PitchShifter::<1>::new(RATE);
+            shifter.set_pitch_semitones(semitones);
+            for &x in input.iter()
+            {
+                shifter.process_sample(RATE, 0, x);
+            }
+            shifter.spectral_balance(0)
+                .unwrap()
+                .map(|[low, mid, high]| high/(low + mid + high))
+        };
+        let [input_unity, wet_unity] = high_share(0.0);
+        let [input_up, wet_up] = high_share(12.0);
+        // At unity the wet reads like the input, an octave up the 3 kHz tone lands in the high band
+        assert!((wet_unity - input_unity).abs() < 0.05, "{} against {} at unity", wet_unity, input_unity);
+        assert!(wet_up > input_up*2.0, "{} against {} an octave up", wet_up, input_up);
+    }
+
+    #[test]
+    fn hann_window_reduces_the_sidebands()
+    {
+        let input = test_signals::multi_tone(RATE, &[(1000.0, 0.5)], 3*RATE as usize/2);
+        let sidebands = |hann: bool| {
+            let mut shifter = PitchShifter::<1>::new(RATE);
+            shifter.configure(&Settings {
+                hann,
+                ..Default::default()
+            });
+            shifter.set_pitch_semitones(5.0);
+            let y: Vec<f64> = input.iter()
+                .map(|&x| shifter.process_sample(RATE, 0, x))
+                .collect();
+            // A second once it has settled, windowed so the measurement doesn't leak, with bins one hertz apart
+            let length = RATE as usize;
+            let y: Vec<f64> = y[y.len() - length..].iter()
+                .enumerate()
+                .map(|(n, y)| y*(PI*n as f64/length as f64).sin().powi(2))
+                .collect();
+            let bin = |f: usize| 2.0*transfer_function::response(RATE, &y, f as f64).norm_sqr()/length as f64;
+            // Whatever leaks further from the shifted tone than the first few sidebands of the sweep through the window, out of
+            // everything. The wrap around the window clicks, spreading it wide, where the Hann window only modulates it gently.
+            let peak = (1200..1500).max_by(|&a, &b| bin(a).total_cmp(&bin(b)))
+                .unwrap();
+            let tone = (peak - 50..=peak + 50).map(bin)
+                .sum::<f64>();
+            let total = y.iter()
+                .map(|y| y*y)
+                .sum::<f64>();
+            1.0 - tone/total
+        };
+        let (plain, hann) = (sidebands(false), sidebands(true));
+        assert!(hann < plain*0.1, "{} of the energy in the sidebands with the Hann window against {} without", hann, plain);
+    }
+
+    #[test]
+    fn every_channel_count_shifts_the_same()
+    {
+        let input = test_signals::multi_tone(RATE, &[(220.0, 0.4), (1234.5, 0.2)], 4096);
+        let mut mono = PitchShifter::<1>::new(RATE);
+        let mut quad = PitchShifter::<4>::new(RATE);
+        mono.set_pitch_semitones(-3.0);
+        quad.set_pitch_semitones(-3.0);
+        for &x in input.iter()
+        {
+            let y = mono.process_sample(RATE, 0, x);
+            for channel in 0..4
+            {
+                assert_eq!(quad.process_sample(RATE, channel, x), y);
+            }
+        }
+    }
+
+    #[test]
+    fn current_pitch_ratio_includes_the_modulation()
+    {
+        let mut shifter = PitchShifter::<1>::new(RATE);
+        shifter.set_pitch(1.0);
+        assert_eq!(shifter.current_pitch_ratio(), 2.0);
+        shifter.set_pitch_modulation(-0.5);
+        assert!((shifter.current_pitch_ratio() - 2.0f64.sqrt()).abs() < 1e-12);
+        shifter.set_pitch_modulation(0.0);
+        assert_eq!(shifter.current_pitch_ratio(), 2.0);
+    }
+}
@@ -0,0 +1,1698 @@
+use std::{f64::consts::TAU, ptr, sync::Arc, time::{Duration, Instant}};
+
+use num::Float;
+use real_time_fir_iir_filters::{iir::first::FirstOrderFilter, Filter};
+use vst::{api::ProcessLevel, host::OpCode, prelude::*, plugin_main};
+
+use crate::{CHANNEL_COUNT, WINDOW_LENGTH};
+use crate::all_pass::AllPass;
+use crate::crossover::LinkwitzRiley;
+use crate::delay::DelayLine;
+use crate::limiter::Limiter;
+use crate::parameters::{band_count, enum_index, routing_product, BasicFilterParameters, Control, StereoMode, BAND_COUNT_MAX, OCTAVES_PER_UNIT_PITCH, HAAS_MAX, PITCH_PER_FINE_PITCH, PRE_DELAY_MAX, SEMITONES_PER_UNIT_PITCH};
+use crate::pitch_shifter::{min_pitch_ratio, HOP_MAX, LOW_CUT_DEFAULT};
+use crate::processor::{Algorithm, AudioProcessor, DcBin, Quality, Settings};
+use crate::resampler::{InternalRate, Resampled};
+use crate::tuner::PitchDetector;
+
+/// Time in seconds to crossfade between the processed output and the plain input when bypass toggles.
+const BYPASS_FADE_TIME: f64 = 0.005;
+
+/// Time in seconds for the pitch to glide to the next semitone in stepped mode.
+const PITCH_STEP_GLIDE_TIME: f64 = 0.01;
+
+/// Crossover frequencies between the low/mid and mid/high bands. With two bands only the first is used.
+const F_CROSSOVER: [f64; BAND_COUNT_MAX - 1] = [500.0, 4000.0];
+
+/// How far each wet decorrelation all-pass stage moves from the identity at full amount. Scaled differently for each channel.
+const DECORRELATION_SPREAD: [f64; 4] = [0.3, 0.7, 1.1, 1.5];
+/// Closest the decorrelation all-passes get to the identity, keeping their poles inside the unit circle.
+const DECORRELATION_A_MAX: f64 = 0.99;
+
+/// Lookahead of the output limiter in seconds. Always part of the latency, so it doesn't change as the limiter toggles.
+const LIMITER_LOOKAHEAD: f64 = 0.003;
+/// Time in seconds for the output limiter to release by 60 dB.
+const LIMITER_RELEASE_TIME: f64 = 0.1;
+/// Output limiter ceiling, at 0 dBFS.
+const LIMITER_CEILING: f64 = 1.0;
+
+/// Pivot frequency of the wet tilt EQ.
+const F_TILT: f64 = 1000.0;
+/// Tilt in dB added by auto tilt for every octave the pitch goes down, making up for the spectrum moving lower.
+const AUTO_TILT_PER_OCTAVE: f64 = 3.0;
+
+/// Cutoff of the input compressor's envelope smoothing.
+const F_COMPRESSOR_ENVELOPE: f64 = 30.0;
+
+/// Time in seconds for the wet to fade in or out as the noise gate opens or closes.
+const NOISE_GATE_FADE_TIME: f64 = 0.005;
+
+/// Share of the block's duration spent processing it that makes the CPU guard double the hop.
+const CPU_GUARD_HIGH: f64 = 0.7;
+/// Share of the block's duration spent processing it that lets the CPU guard halve the hop again.
+const CPU_GUARD_LOW: f64 = 0.25;
+/// Time in seconds the CPU guard waits after changing the hop before it changes it again, so a load right at a threshold doesn't
+/// flip the quality back and forth.
+const CPU_GUARD_HOLD_OFF: f64 = 0.5;
+
+/// Time constant in seconds of the input and output level meters.
+const RMS_TIME: f64 = 0.3;
+
+/// Share of the wow rate it wanders by, either way.
+const WOW_DRIFT: f64 = 0.3;
+/// Time in seconds for the wow rate to wander to its next random offset.
+const WOW_DRIFT_TIME: f64 = 2.0;
+/// Start of the wow's random drift, fixed so renders come out the same every time.
+const WOW_DRIFT_SEED: u32 = 0x9e3779b9;
+
+/// Parameter-derived values that stay fixed for a block, see [`PitchShifterPlugin::block_controls`].
+struct BlockControls
+{
+    mix: f64,
+    wet_gain: f64,
+    bypass: f64,
+    dfade: f64,
+    compressor: bool,
+    compressor_threshold: f64,
+    compressor_ratio: f64,
+    input_stage_on_dry: bool,
+    envelope_depth: f64,
+    envelope_invert: bool,
+    omega_attack: f64,
+    omega_release: f64,
+    pre_delay: usize,
+    haas: usize,
+    drive: Option<f64>,
+    saturation_post: bool,
+    limiter: bool,
+    ceiling: f64,
+    release: f64,
+    band_count: usize,
+    /// Whether the wet goes through the decorrelation all-passes at all.
+    decorrelate: bool,
+    tilt: f64,
+    tilt_gain: f64,
+    /// How much the wet is delayed to make up the difference between the processors' current latency and their worst case.
+    wet_padding: usize,
+    /// How much the dry is delayed to line up with the wet, the worst case latency of the processors.
+    dry_padding: usize,
+    /// Latency of the whole plugin, which the input is delayed by to line up with the output while bypassed.
+    total_latency: usize,
+    input_routing: [[f64; CHANNEL_COUNT]; CHANNEL_COUNT],
+    output_routing: [[f64; CHANNEL_COUNT]; CHANNEL_COUNT],
+    /// Input levels the noise gate opens and closes at, if it's on.
+    noise_gate: Option<[f64; 2]>,
+    dgate: f64,
+    /// Input gain of each shifter channel.
+    trim: [f64; CHANNEL_COUNT],
+    /// Polarity of each channel's wet, 1.0 or -1.0.
+    wet_polarity: [f64; CHANNEL_COUNT],
+    /// Depth in octaves, phase at the start of the block and phase step per sample of the wow and the flutter, if either has depth.
+    wow_flutter: Option<[[f64; 3]; 2]>,
+    /// Level the sidechain opens the wet above, and the attack and release cutoffs of its envelope, if the sidechain gate is on.
+    sidechain_gate: Option<[f64; 3]>
+}
+
+struct PitchShifterPlugin
+{
+    host: HostCallback,
+    pub param: Arc<BasicFilterParameters>,
+    /// One processor per band for every algorithm, made up front so switching algorithms doesn't allocate on the audio thread.
+    processors: [[Resampled; BAND_COUNT_MAX]; Algorithm::VARIANT_COUNT],
+    crossover: [[LinkwitzRiley; BAND_COUNT_MAX]; CHANNEL_COUNT],
+    compressor_envelope: [FirstOrderFilter<f64>; CHANNEL_COUNT],
+    dry_delay: [DelayLine; CHANNEL_COUNT],
+    wet_delay: [DelayLine; CHANNEL_COUNT],
+    /// The plain input, delayed to come out with the output it crossfades with when bypassed.
+    bypass_delay: [DelayLine; CHANNEL_COUNT],
+    pre_delay: [DelayLine; CHANNEL_COUNT],
+    haas: [DelayLine; CHANNEL_COUNT],
+    decorrelation: [[AllPass; DECORRELATION_SPREAD.len()]; CHANNEL_COUNT],
+    limiter: [Limiter; CHANNEL_COUNT],
+    tilt: [FirstOrderFilter<f64>; CHANNEL_COUNT],
+    /// Follows the input level for the envelope controlled mix.
+    mix_envelope: [FirstOrderFilter<f64>; CHANNEL_COUNT],
+    mix_level: [f64; CHANNEL_COUNT],
+    /// Follows the sidechain level for the sidechain gate.
+    sidechain_envelope: [FirstOrderFilter<f64>; CHANNEL_COUNT],
+    sidechain_level: [f64; CHANNEL_COUNT],
+    /// Gain of the wet through the sidechain gate.
+    sidechain_fade: [f64; CHANNEL_COUNT],
+    /// Whether the sidechain has carried any signal yet. It's always declared, so hosts with nothing routed to it pass silence.
+    sidechain_connected: bool,
+    /// Smoothed mean square of each channel's input and output, for the level meters.
+    input_power: [f64; CHANNEL_COUNT],
+    output_power: [f64; CHANNEL_COUNT],
+    pitch_detector: PitchDetector<WINDOW_LENGTH>,
+    /// Output of each shifter channel for the current block, before the output routing.
+    routed: [Vec<f64>; CHANNEL_COUNT],
+    /// Coarse pitch in octaves as it glides between semitones in stepped mode.
+    pitch_stepped: f64,
+    /// Shift in octaves currently applied by auto-tune.
+    auto_tune_correction: f64,
+    algorithm: Algorithm,
+    band_count: usize,
+    bypass_fade: [f64; CHANNEL_COUNT],
+    noise_gate_open: [bool; CHANNEL_COUNT],
+    /// Gain of the wet through the noise gate. The shifters only resynthesize while it's above zero.
+    noise_gate_fade: [f64; CHANNEL_COUNT],
+    /// Smoothed share of each block's duration spent processing it.
+    cpu_load: f64,
+    /// Smallest hop the CPU guard allows right now.
+    auto_hop: usize,
+    /// Samples left until the CPU guard may change the hop again.
+    cpu_hold_off: usize,
+    /// Phase of the wow and the flutter in radians.
+    wow_flutter_phase: [f64; 2],
+    /// Offset of the wow rate as a share of [`WOW_DRIFT`], gliding between random targets.
+    wow_drift: f64,
+    wow_drift_state: u32,
+    rate: f64
+}
+
+impl PitchShifterPlugin
+{
+    /// Switches over to the processors of the given algorithm, starting them from a clean state.
+    fn set_algorithm(&mut self, algorithm: Algorithm)
+    {
+        let settings = self.settings();
+        for processor in self.processors[algorithm as usize].iter_mut()
+        {
+            processor.reset();
+            processor.configure(&settings);
+        }
+        self.algorithm = algorithm;
+    }
+
+    /// The processors of the algorithm in use, one per band.
+    fn processors(&self) -> &[Resampled; BAND_COUNT_MAX]
+    {
+        &self.processors[self.algorithm as usize]
+    }
+
+    /// Upper bound on the latency of the processors at `rate`, with any algorithm, settings and internal rate.
+    fn max_latency(rate: f64) -> usize
+    {
+        Algorithm::VARIANTS.into_iter()
+            .map(|algorithm| Resampled::<CHANNEL_COUNT>::max_latency(rate, algorithm.max_latency()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn crossover() -> [LinkwitzRiley; BAND_COUNT_MAX]
+    {
+        // The last one is only there to give the low band the same phase shift as the mid/high split
+        let [f0, f1] = F_CROSSOVER;
+        [LinkwitzRiley::new(f0*TAU), LinkwitzRiley::new(f1*TAU), LinkwitzRiley::new(f1*TAU)]
+    }
+
+    /// Which processors handle the bands, for a given number of bands. The last one always takes the highest band.
+    fn active_bands(band_count: usize) -> &'static [usize]
+    {
+        match band_count
+        {
+            1 => &[0],
+            2 => &[0, 2],
+            _ => &[0, 1, 2]
+        }
+    }
+
+    fn pre_delay(rate: f64) -> [DelayLine; CHANNEL_COUNT]
+    {
+        [(); CHANNEL_COUNT].map(|()| DelayLine::new((PRE_DELAY_MAX as f64*0.001*rate).ceil() as usize))
+    }
+
+    fn haas(rate: f64) -> [DelayLine; CHANNEL_COUNT]
+    {
+        [(); CHANNEL_COUNT].map(|()| DelayLine::new((HAAS_MAX as f64*0.001*rate).ceil() as usize))
+    }
+
+    /// Pre-delay on the whole wet and Haas delay on the right of it, in samples.
+    fn wet_delays(&self) -> [usize; 2]
+    {
+        [&self.param.pre_delay, &self.param.haas].map(|delay| (delay.get() as f64*0.001*self.rate).round() as usize)
+    }
+
+    fn limiter(rate: f64) -> [Limiter; CHANNEL_COUNT]
+    {
+        [(); CHANNEL_COUNT].map(|()| Limiter::new((LIMITER_LOOKAHEAD*rate).round() as usize))
+    }
+
+    fn bypass_delay(rate: f64) -> [DelayLine; CHANNEL_COUNT]
+    {
+        [(); CHANNEL_COUNT].map(|()| DelayLine::new(Self::max_latency(rate) + (LIMITER_LOOKAHEAD*rate).round() as usize))
+    }
+
+    /// Delays long enough to pad the dry or the wet out to the worst case latency of the processors.
+    fn latency_padding(rate: f64) -> [DelayLine; CHANNEL_COUNT]
+    {
+        [(); CHANNEL_COUNT].map(|()| DelayLine::new(Self::max_latency(rate)))
+    }
+
+    /// Total latency of the plugin. The host only reads it once, so it's the worst case of every setting that adds latency,
+    /// with the wet and dry padded out to it.
+    fn latency(&self) -> usize
+    {
+        Self::max_latency(self.rate) + self.limiter[0].lookahead()
+    }
+
+    /// Whether the host says it's rendering offline, rather than playing back in realtime.
+    fn offline(&self) -> bool
+    {
+        match self.host.raw_callback()
+        {
+            Some(callback) => callback(self.host.raw_effect(), OpCode::GetCurrentProcessLevel.into(), 0, 0, ptr::null_mut(), 0.0) == ProcessLevel::Offline as isize,
+            None => false
+        }
+    }
+
+    fn settings(&self) -> Settings
+    {
+        // Asking the host is a callback, so only bother if it would make a difference
+        let aliasing = if self.param.offline_hq.get() >= 0.5 && self.offline()
+        {
+            Quality::High
+        }
+        else
+        {
+            Quality::VARIANTS[enum_index(self.param.aliasing.get(), Quality::VARIANT_COUNT)]
+        };
+        Settings {
+            linear_phase: enum_index(self.param.phase.get(), 2) == 1,
+            aliasing,
+            dc_block: self.param.dc_block.get() >= 0.5,
+            normalize: self.param.normalize.get() >= 0.5,
+            gate_threshold: if self.param.spectral_gate.get() >= 0.5 {10.0f64.powf(self.param.gate_threshold.get() as f64/20.0)} else {0.0},
+            gate_smoothing: self.param.gate_smoothing.get() as f64*0.001,
+            resynthesis_only: self.param.resynthesis_only.get() >= 0.5,
+            hop: (1 << enum_index(self.param.hop.get(), 4)).max(self.auto_hop),
+            low_cut: self.param.low_cut.get() as f64,
+            dc_bin: DcBin::VARIANTS[enum_index(self.param.dc_bin.get(), DcBin::VARIANT_COUNT)],
+            cutoff_glide: self.param.cutoff_glide.get() as f64*0.001,
+            hann: self.param.hann_window.get() >= 0.5
+        }
+    }
+
+    /// Converts an input sample, replacing NaN and infinity with silence so they can't poison the filter states.
+    fn sample<F>(x: F) -> f64
+    where
+        F: Float
+    {
+        x.to_f64()
+            .filter(|x| x.is_finite())
+            .unwrap_or(0.0)
+    }
+
+    /// Soft clips `x` with a gain of `drive`. Unity gain at small levels, so more drive only adds more saturation.
+    fn saturate(x: f64, drive: f64) -> f64
+    {
+        (x*drive).tanh()/drive
+    }
+
+    /// Feeds the detector a mono sum of the input block, and publishes what it hears.
+    fn detect_pitch<F>(&mut self, buffer: &mut AudioBuffer<F>) -> Option<f64>
+    where
+        F: Float
+    {
+        let (inputs, _) = buffer.split();
+        // Hosts may hand over no inputs at all, which leaves nothing to hear
+        if inputs.is_empty()
+        {
+            self.param.detected_pitch.set(0.0);
+            return None
+        }
+        for i in 0..inputs.get(0).len()
+        {
+            let channels = inputs.len().min(CHANNEL_COUNT);
+            let x = (0..channels).map(|channel| Self::sample(inputs.get(channel)[i]))
+                .sum::<f64>()/channels as f64;
+            self.pitch_detector.push(x);
+        }
+        let pitch = self.pitch_detector.detect(self.rate);
+        self.param.detected_pitch.set(pitch.unwrap_or(0.0) as f32);
+        pitch
+    }
+
+    /// Correction in octaves that puts the detected pitch, shifted by `octaves`, on the nearest semitone of the equal-tempered chromatic
+    /// scale tuned to A440. `strength` goes from 0.0 for none to 1.0 for all the way there.
+    fn auto_tune_target(pitch: f64, octaves: f64, strength: f64) -> f64
+    {
+        let semitones = 12.0*((pitch/440.0).log2() + octaves);
+        (semitones.round() - semitones)/12.0*strength
+    }
+
+    /// Moves the auto-tune correction toward [`auto_tune_target`](Self::auto_tune_target) for the shift `octaves`.
+    fn auto_tune(&mut self, pitch: Option<f64>, octaves: f64, block_size: usize)
+    {
+        // Hold the last correction through silence and unpitched input
+        if let Some(pitch) = pitch
+        {
+            let target = Self::auto_tune_target(pitch, octaves, self.param.auto_tune_strength.get() as f64);
+            let speed = self.param.auto_tune_speed.get() as f64*0.001*self.rate;
+            let alpha = if speed > block_size as f64 {block_size as f64/speed} else {1.0};
+            self.auto_tune_correction += (target - self.auto_tune_correction)*alpha;
+        }
+    }
+
+    /// Works out everything the block needs from the parameters, and brings the processors up to date with them.
+    ///
+    /// This is the control rate part of [`process`](Self::process), so the sample loops only have audio and smoothing left to do.
+    fn block_controls<F>(&mut self, buffer: &mut AudioBuffer<F>) -> BlockControls
+    where
+        F: Float
+    {
+        let pitch = (self.param.pitch.get()*OCTAVES_PER_UNIT_PITCH) as f64;
+        let pitch = if self.param.pitch_stepped.get() >= 0.5
+        {
+            // Snap to semitones, but glide there so automation doesn't jump
+            let semitones_per_octave = (SEMITONES_PER_UNIT_PITCH/OCTAVES_PER_UNIT_PITCH) as f64;
+            let target = (pitch*semitones_per_octave).round()/semitones_per_octave;
+            let alpha = (buffer.samples() as f64/(PITCH_STEP_GLIDE_TIME*self.rate)).min(1.0);
+            self.pitch_stepped += (target - self.pitch_stepped)*alpha;
+            if (target - self.pitch_stepped).abs() < 1e-6
+            {
+                // Land exactly on the semitone
+                self.pitch_stepped = target;
+            }
+            self.pitch_stepped
+        }
+        else
+        {
+            self.pitch_stepped = pitch;
+            pitch
+        };
+        let fine = (self.param.pitch_fine.get()*PITCH_PER_FINE_PITCH*OCTAVES_PER_UNIT_PITCH) as f64;
+        let octaves = if enum_index(self.param.fine_link.get(), 2) == 1 {fine} else {pitch + fine};
+        // Both on cancel out
+        let octaves = octaves
+            + if self.param.octave_up.get() >= 0.5 {1.0} else {0.0}
+            - if self.param.octave_down.get() >= 0.5 {1.0} else {0.0};
+
+        // On a send, the whole point is the wet alone, so automation can't leak any dry in
+        let send_mode = self.param.send_mode.get() >= 0.5;
+        let mix = if send_mode {1.0} else {self.param.mix.get() as f64};
+        let wet_gain = if send_mode {1.0} else {self.param.wet_gain.get() as f64};
+        let bypass = if self.param.bypass.get() >= 0.5 {1.0} else {0.0};
+        let dfade = 1.0/(BYPASS_FADE_TIME*self.rate);
+
+        let compressor = self.param.compressor.get() >= 0.5;
+        let compressor_threshold = 10.0f64.powf(self.param.compressor_threshold.get() as f64/20.0);
+        let compressor_ratio = self.param.compressor_ratio.get() as f64;
+        let input_stage_on_dry = self.param.input_stage_on_dry.get() >= 0.5;
+
+        let envelope_depth = if send_mode {0.0} else {self.param.envelope_depth.get() as f64};
+        let envelope_invert = self.param.envelope_invert.get() >= 0.5;
+        // Cutoffs matching the time constants
+        let omega_attack = 1000.0/self.param.envelope_attack.get() as f64;
+        let omega_release = 1000.0/self.param.envelope_release.get() as f64;
+
+        // Meant to be heard, so these are left out of the reported latency
+        let [pre_delay, haas] = self.wet_delays();
+
+        let drive = self.param.drive.get() as f64;
+        let drive = if drive > 0.0 {Some(10.0f64.powf(drive/20.0))} else {None};
+        let saturation_post = enum_index(self.param.saturation_placement.get(), 2) == 1;
+
+        let limiter = self.param.limiter.get() >= 0.5;
+        let ceiling = 10.0f64.powf(self.param.ceiling.get() as f64/20.0);
+        let release = 0.001f64.powf(1.0/(LIMITER_RELEASE_TIME*self.rate));
+
+        let algorithm = Algorithm::from(self.param.algorithm.get());
+        if algorithm != self.algorithm
+        {
+            self.set_algorithm(algorithm);
+        }
+
+        let band_count = band_count(self.param.bands.get());
+        if band_count != self.band_count
+        {
+            for processor in self.processors[self.algorithm as usize].iter_mut()
+            {
+                processor.reset();
+            }
+            self.crossover = [(); CHANNEL_COUNT].map(|()| Self::crossover());
+            self.band_count = band_count;
+        }
+
+        let auto_tune = self.param.auto_tune.get() >= 0.5;
+        let pitch = if auto_tune || self.param.tuner.get() >= 0.5
+        {
+            self.detect_pitch(buffer)
+        }
+        else
+        {
+            self.param.detected_pitch.set(0.0);
+            None
+        };
+        let octaves = if auto_tune
+        {
+            self.auto_tune(pitch, octaves, buffer.samples());
+            octaves + self.auto_tune_correction
+        }
+        else
+        {
+            self.auto_tune_correction = 0.0;
+            octaves
+        };
+
+        let band_pitch = if band_count == 1
+        {
+            [0.0; BAND_COUNT_MAX]
+        }
+        else
+        {
+            [&self.param.pitch_low, &self.param.pitch_mid, &self.param.pitch_high].map(|pitch| (pitch.get()*OCTAVES_PER_UNIT_PITCH) as f64)
+        };
+        let settings = self.settings();
+        let channel_link = self.param.channel_link.get() >= 0.5;
+        // Only the SDFT's band collapses, varispeed just plays back slower
+        let min_octaves = if self.param.pitch_limit.get() >= 0.5 && self.algorithm == Algorithm::Sdft
+        {
+            let low_cut = if settings.low_cut > 0.0 {settings.low_cut} else {LOW_CUT_DEFAULT};
+            min_pitch_ratio(low_cut).log2()
+        }
+        else
+        {
+            f64::NEG_INFINITY
+        };
+        let mut pitch_limited = false;
+        let internal_rate = InternalRate::VARIANTS[enum_index(self.param.internal_rate.get(), InternalRate::VARIANT_COUNT)];
+        for (processor, band_pitch) in self.processors[self.algorithm as usize].iter_mut()
+            .zip(band_pitch)
+        {
+            processor.set_internal_rate(internal_rate);
+            processor.configure(&settings);
+            // Any CV, wow or flutter is applied again sample by sample
+            processor.set_pitch_modulation(0.0);
+            pitch_limited |= octaves + band_pitch < min_octaves;
+            processor.set_pitch((octaves + band_pitch).max(min_octaves));
+            if channel_link
+            {
+                processor.link_channels();
+            }
+        }
+        self.param.pitch_limited.set(if pitch_limited {1.0} else {0.0});
+        let decorrelation = self.param.decorrelation.get() as f64;
+        let decorrelate = decorrelation > 0.0;
+        for (channel, all_passes) in self.decorrelation.iter_mut()
+            .enumerate()
+        {
+            for (all_pass, spread) in all_passes.iter_mut()
+                .zip(DECORRELATION_SPREAD)
+            {
+                all_pass.a = (1.0 - decorrelation*spread*(channel + 1) as f64/CHANNEL_COUNT as f64).clamp(-DECORRELATION_A_MAX, DECORRELATION_A_MAX);
+                // Skipped, so they start from silence once they're back in
+                if !decorrelate
+                {
+                    all_pass.reset();
+                }
+            }
+        }
+
+        let tilt = self.param.tilt.get() as f64 + if self.param.auto_tilt.get() >= 0.5 {-octaves*AUTO_TILT_PER_OCTAVE} else {0.0};
+        // Half the tilt is a cut below the pivot, half a boost above
+        let tilt_gain = 10.0f64.powf(tilt/40.0);
+
+        let dry_padding = Self::max_latency(self.rate);
+        let wet_padding = dry_padding - self.processors()[0].latency().min(dry_padding);
+        let total_latency = self.latency();
+        self.param.latency.set(total_latency as f32);
+
+        // Resolve the routing once, so the loops below are just sums. The stereo mode goes inside the user's routing.
+        let [input_routing, output_routing] = self.param.routing();
+        let [mode_input_routing, mode_output_routing] = StereoMode::VARIANTS[enum_index(self.param.stereo_mode.get(), StereoMode::VARIANT_COUNT)].routing();
+        let [input_routing, output_routing] = [
+            routing_product(&mode_input_routing, &input_routing),
+            routing_product(&output_routing, &mode_output_routing)
+        ].map(|routing| routing.map(|row| row.map(|gain| gain as f64)));
+
+        let noise_gate = if self.param.noise_gate.get() >= 0.5
+        {
+            let threshold = self.param.noise_gate_threshold.get() as f64;
+            let hysteresis = self.param.noise_gate_hysteresis.get() as f64;
+            Some([threshold, threshold - hysteresis].map(|level| 10.0f64.powf(level/20.0)))
+        }
+        else
+        {
+            None
+        };
+        let dgate = 1.0/(NOISE_GATE_FADE_TIME*self.rate);
+
+        let trim = [&self.param.trim_left, &self.param.trim_right].map(|trim| 10.0f64.powf(trim.get() as f64/20.0));
+        let wet_polarity = [&self.param.invert_wet_left, &self.param.invert_wet_right].map(|invert| if invert.get() >= 0.5 {-1.0} else {1.0});
+        let wow_flutter = self.wow_flutter(buffer.samples());
+        let sidechain_gate = if self.param.sidechain_gate.get() >= 0.5
+        {
+            Some([
+                10.0f64.powf(self.param.sidechain_threshold.get() as f64/20.0),
+                1000.0/self.param.sidechain_attack.get() as f64,
+                1000.0/self.param.sidechain_release.get() as f64
+            ])
+        }
+        else
+        {
+            None
+        };
+
+        BlockControls {
+            mix,
+            wet_gain,
+            bypass,
+            dfade,
+            compressor,
+            compressor_threshold,
+            compressor_ratio,
+            input_stage_on_dry,
+            envelope_depth,
+            envelope_invert,
+            omega_attack,
+            omega_release,
+            pre_delay,
+            haas,
+            drive,
+            saturation_post,
+            limiter,
+            ceiling,
+            release,
+            band_count,
+            decorrelate,
+            tilt,
+            tilt_gain,
+            wet_padding,
+            dry_padding,
+            total_latency,
+            input_routing,
+            output_routing,
+            noise_gate,
+            dgate,
+            trim,
+            wet_polarity,
+            wow_flutter,
+            sidechain_gate
+        }
+    }
+
+    /// Xorshift, from -1 to 1.
+    fn random(&mut self) -> f64
+    {
+        let state = &mut self.wow_drift_state;
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state as f64/u32::MAX as f64*2.0 - 1.0
+    }
+
+    /// Steps the wow and flutter over a block, returning how they run during it. `None` while neither has any depth.
+    fn wow_flutter(&mut self, samples: usize) -> Option<[[f64; 3]; 2]>
+    {
+        let depth = [&self.param.wow_depth, &self.param.flutter_depth].map(|depth| depth.get() as f64/1200.0);
+        if depth == [0.0; 2]
+        {
+            return None
+        }
+        let alpha = (samples as f64/(WOW_DRIFT_TIME*self.rate)).min(1.0);
+        let target = self.random();
+        self.wow_drift += (target - self.wow_drift)*alpha;
+        let rate = [
+            self.param.wow_rate.get() as f64*(1.0 + WOW_DRIFT*self.wow_drift),
+            self.param.flutter_rate.get() as f64
+        ];
+
+        let mut wow_flutter = [[0.0; 3]; 2];
+        for ((wow_flutter, phase), (depth, rate)) in wow_flutter.iter_mut()
+            .zip(self.wow_flutter_phase.iter_mut())
+            .zip(depth.into_iter().zip(rate))
+        {
+            let dphase = TAU*rate/self.rate;
+            *wow_flutter = [depth, *phase, dphase];
+            *phase = (*phase + dphase*samples as f64) % TAU;
+        }
+        Some(wow_flutter)
+    }
+
+    /// Sums the spectral balance of the active bands, averaged over the channels, into the readout.
+    fn publish_spectral_balance(&self, band_count: usize)
+    {
+        let mut balance = [[0.0; 3]; 2];
+        for &band in Self::active_bands(band_count)
+        {
+            for channel in 0..CHANNEL_COUNT
+            {
+                if let Some(band_balance) = self.processors()[band].spectral_balance(channel)
+                {
+                    for (side, band_side) in balance.iter_mut()
+                        .zip(band_balance)
+                    {
+                        for (energy, band_energy) in side.iter_mut()
+                            .zip(band_side)
+                        {
+                            *energy += band_energy/CHANNEL_COUNT as f64;
+                        }
+                    }
+                }
+            }
+        }
+        for (atomics, side) in self.param.spectral_balance.iter()
+            .zip(balance)
+        {
+            for (atomic, energy) in atomics.iter()
+                .zip(side)
+            {
+                atomic.set(energy as f32);
+            }
+        }
+    }
+
+    /// Trades quality for CPU when a block took too long to process, and back again once there's room.
+    fn guard_cpu(&mut self, elapsed: Duration, samples: usize)
+    {
+        // Rendering offline, there's no deadline to miss
+        if self.param.cpu_guard.get() < 0.5 || samples == 0 || self.offline()
+        {
+            self.cpu_load = 0.0;
+            self.auto_hop = 1;
+            self.cpu_hold_off = 0;
+        }
+        else
+        {
+            let load = elapsed.as_secs_f64()*self.rate/samples as f64;
+            self.cpu_load += (load - self.cpu_load)*0.1;
+            self.cpu_hold_off = self.cpu_hold_off.saturating_sub(samples);
+            // Assume the load follows the hop, so the smoothing doesn't step it again before it catches up
+            let hop = if self.cpu_hold_off > 0
+            {
+                self.auto_hop
+            }
+            else if self.cpu_load > CPU_GUARD_HIGH && self.auto_hop < HOP_MAX
+            {
+                self.cpu_load *= 0.5;
+                self.auto_hop*2
+            }
+            else if self.cpu_load < CPU_GUARD_LOW && self.auto_hop > 1
+            {
+                self.cpu_load *= 2.0;
+                self.auto_hop/2
+            }
+            else
+            {
+                self.auto_hop
+            };
+            if hop != self.auto_hop
+            {
+                self.auto_hop = hop;
+                self.cpu_hold_off = (CPU_GUARD_HOLD_OFF*self.rate) as usize;
+            }
+        }
+        self.param.effective_hop.set((1usize << enum_index(self.param.hop.get(), 4)).max(self.auto_hop) as f32);
+    }
+
+    fn process<F>(&mut self, buffer: &mut AudioBuffer<F>)
+    where
+        F: Float
+    {
+        let start = Instant::now();
+        let BlockControls {
+            mix,
+            wet_gain,
+            bypass,
+            dfade,
+            compressor,
+            compressor_threshold,
+            compressor_ratio,
+            input_stage_on_dry,
+            envelope_depth,
+            envelope_invert,
+            omega_attack,
+            omega_release,
+            pre_delay,
+            haas,
+            drive,
+            saturation_post,
+            limiter,
+            ceiling,
+            release,
+            band_count,
+            decorrelate,
+            tilt,
+            tilt_gain,
+            wet_padding,
+            dry_padding,
+            total_latency,
+            input_routing,
+            output_routing,
+            noise_gate,
+            dgate,
+            trim,
+            wet_polarity,
+            wow_flutter,
+            sidechain_gate
+        } = self.block_controls(buffer);
+
+        let samples = buffer.samples();
+        for routed in self.routed.iter_mut()
+        {
+            // Only if the host went over the block size it promised
+            if routed.len() < samples
+            {
+                routed.resize(samples, 0.0);
+            }
+        }
+        let (inputs, mut outputs) = buffer.split();
+        // The CV only counts if the host connected it and there's depth to apply it with
+        let cv_depth = self.param.cv_depth.get() as f64;
+        let cv = if cv_depth > 0.0 && inputs.len() > CHANNEL_COUNT {Some(inputs.get(CHANNEL_COUNT))} else {None};
+        let drms = (1.0/(RMS_TIME*self.rate)).min(1.0);
+        // Same for the sidechain, without one the wet is left ungated. One that's never been anything but silent isn't connected.
+        let sidechain = match sidechain_gate
+        {
+            Some(sidechain_gate) if inputs.len() > CHANNEL_COUNT + 1 => {
+                let sidechain = inputs.get(CHANNEL_COUNT + 1);
+                self.sidechain_connected |= sidechain[..samples].iter()
+                    .any(|&x| Self::sample(x) != 0.0);
+                if self.sidechain_connected {Some((sidechain, sidechain_gate))} else {None}
+            },
+            _ => None
+        };
+
+        for (((channel, routed), input_routing), [crossover0, crossover1, crossover_all_pass]) in (0..CHANNEL_COUNT).zip(self.routed.iter_mut())
+            .zip(input_routing)
+            .zip(self.crossover.iter_mut())
+        {
+            let compressor_envelope = &mut self.compressor_envelope[channel];
+            let dry_delay = &mut self.dry_delay[channel];
+            let wet_delay = &mut self.wet_delay[channel];
+            let pre_delay_line = &mut self.pre_delay[channel];
+            let haas_line = &mut self.haas[channel];
+            // The first channel leads, the others lag behind it
+            let haas = if channel == 0 {0} else {haas};
+            let decorrelation = &mut self.decorrelation[channel];
+            let tilt_filter = &mut self.tilt[channel];
+            let mix_envelope = &mut self.mix_envelope[channel];
+            let mix_level = &mut self.mix_level[channel];
+            let sidechain_envelope = &mut self.sidechain_envelope[channel];
+            let sidechain_level = &mut self.sidechain_level[channel];
+            let sidechain_fade = &mut self.sidechain_fade[channel];
+            let input_power = &mut self.input_power[channel];
+            let noise_gate_open = &mut self.noise_gate_open[channel];
+            let noise_gate_fade = &mut self.noise_gate_fade[channel];
+            let trim = trim[channel];
+            let wet_polarity = wet_polarity[channel];
+            for (i, routed) in routed[..samples].iter_mut()
+                .enumerate()
+            {
+                let x = input_routing.iter()
+                    .take(inputs.len())
+                    .enumerate()
+                    .map(|(j, gain)| gain*Self::sample(inputs.get(j)[i]))
+                    .sum::<f64>()*trim;
+                *input_power += (x*x - *input_power)*drms;
+
+                // Tame hot inputs before analysis, so the resynthesis doesn't overshoot. Only the wet sees this.
+                let [envelope, _] = compressor_envelope.filter(self.rate, x.abs());
+                let z = if compressor && envelope > compressor_threshold
+                {
+                    x*(envelope/compressor_threshold).powf(compressor_ratio.recip() - 1.0)
+                }
+                else
+                {
+                    x
+                };
+
+                // Before the shift it colors what gets analysed, so the harmonics get shifted along with everything else
+                let z_wet = match drive
+                {
+                    Some(drive) if !saturation_post => Self::saturate(z, drive),
+                    _ => z
+                };
+
+                let bands = match band_count
+                {
+                    1 => [z_wet, 0.0, 0.0],
+                    2 => {
+                        let [low, high] = crossover0.split(self.rate, z_wet);
+                        [low, 0.0, high]
+                    },
+                    _ => {
+                        let [low, high] = crossover0.split(self.rate, z_wet);
+                        let [mid, high] = crossover1.split(self.rate, high);
+                        [crossover_all_pass.all_pass(self.rate, low), mid, high]
+                    }
+                };
+                // Opens above the threshold and closes below the hysteresis, holding in between
+                *noise_gate_open = match noise_gate
+                {
+                    Some([open, close]) => if *noise_gate_open {envelope >= close} else {envelope > open},
+                    None => true
+                };
+                *noise_gate_fade = (*noise_gate_fade + if *noise_gate_open {dgate} else {-dgate}).clamp(0.0, 1.0);
+                let resynthesize = *noise_gate_fade > 0.0;
+
+                let modulation = (cv.is_some() || wow_flutter.is_some()).then(|| {
+                    cv.map_or(0.0, |cv| Self::sample(cv[i]).clamp(-1.0, 1.0)*cv_depth)
+                        + wow_flutter.map_or(0.0, |wow_flutter| wow_flutter.into_iter()
+                            .map(|[depth, phase, dphase]| depth*(phase + dphase*i as f64).sin())
+                            .sum::<f64>()
+                        )
+                });
+
+                // Keep the processors fed while bypassed, so they're warmed up once bypass is released. While gated they only
+                // analyse, so they pick up where the input is once the gate opens.
+                let y = Self::active_bands(band_count).iter()
+                    .map(|&band| {
+                        let processor = &mut self.processors[self.algorithm as usize][band];
+                        if let Some(modulation) = modulation
+                        {
+                            processor.set_pitch_modulation(modulation);
+                        }
+                        if resynthesize
+                        {
+                            processor.process_sample(self.rate, channel, bands[band])
+                        }
+                        else
+                        {
+                            processor.analyse_sample(self.rate, channel, bands[band]);
+                            0.0
+                        }
+                    }).sum::<f64>()*(*noise_gate_fade);
+                let y = wet_delay.delay(y, wet_padding);
+                let y = match drive
+                {
+                    Some(drive) if saturation_post => Self::saturate(y, drive),
+                    _ => y
+                };
+                let y = if decorrelate
+                {
+                    decorrelation.iter_mut()
+                        .fold(y, |y, all_pass| all_pass.filter(y))
+                }
+                else
+                {
+                    y
+                };
+                let y = if tilt != 0.0
+                {
+                    // The low and high outputs add back up to the input, so this is flat with no tilt
+                    let [low, high] = tilt_filter.filter(self.rate, y);
+                    low/tilt_gain + high*tilt_gain
+                }
+                else
+                {
+                    y
+                };
+
+                let y = pre_delay_line.delay(y, pre_delay);
+                let y = haas_line.delay(y, haas);
+
+                let mix = if envelope_depth > 0.0
+                {
+                    // The smoothing speeds up while the level rises, and slows down while it falls
+                    mix_envelope.omega = if x.abs() > *mix_level {omega_attack} else {omega_release};
+                    let [level, _] = mix_envelope.filter(self.rate, x.abs());
+                    *mix_level = level;
+                    let level = level.min(1.0);
+                    mix*(1.0 - envelope_depth + envelope_depth*if envelope_invert {1.0 - level} else {level})
+                }
+                else
+                {
+                    mix
+                };
+
+                let sidechain_open = match sidechain
+                {
+                    Some((sidechain, [threshold, omega_attack, omega_release])) => {
+                        let level = Self::sample(sidechain[i]).abs();
+                        sidechain_envelope.omega = if level > *sidechain_level {omega_attack} else {omega_release};
+                        let [level, _] = sidechain_envelope.filter(self.rate, level);
+                        *sidechain_level = level;
+                        level > threshold
+                    },
+                    None => true
+                };
+                *sidechain_fade = (*sidechain_fade + if sidechain_open {dgate} else {-dgate}).clamp(0.0, 1.0);
+
+                // The dry is always delayed to line up with the wet, so the reported latency holds with any mix
+                let dry = dry_delay.delay(if input_stage_on_dry {z} else {x}, dry_padding);
+
+                *routed = (1.0 - mix)*dry + mix*wet_gain*wet_polarity*(*sidechain_fade)*y;
+            }
+        }
+
+        for ((((channel, output_routing), limiter_channel), output_power), (bypass_fade, bypass_delay)) in (0..outputs.len().min(CHANNEL_COUNT)).zip(output_routing)
+            .zip(self.limiter.iter_mut())
+            .zip(self.output_power.iter_mut())
+            .zip(self.bypass_fade.iter_mut().zip(self.bypass_delay.iter_mut()))
+        {
+            for (i, output_sample) in outputs.get_mut(channel)
+                .iter_mut()
+                .enumerate()
+            {
+                let output = output_routing.iter()
+                    .zip(self.routed.iter())
+                    .map(|(gain, routed)| gain*routed[i])
+                    .sum::<f64>();
+                // Last, so nothing the routing sums up can get past it. Off, it still delays by the lookahead.
+                let output = limiter_channel.limit(output, if limiter {LIMITER_CEILING} else {f64::INFINITY}, release);
+
+                // Plain safety clamp, whatever the limiter is up to
+                let output = output.clamp(-ceiling, ceiling);
+
+                // Bypassed, the input comes out as it went in, past all of the above
+                let x = if channel < inputs.len() {Self::sample(inputs.get(channel)[i])} else {0.0};
+                let x = bypass_delay.delay(x, total_latency);
+                *bypass_fade = (*bypass_fade + (bypass - *bypass_fade).clamp(-dfade, dfade)).clamp(0.0, 1.0);
+                let output = output*(1.0 - *bypass_fade) + x*(*bypass_fade);
+
+                // Scrub in case anything went non-finite anyway, rather than pass it on down the chain
+                let output = if output.is_finite() {output} else {0.0};
+                *output_power += (output*output - *output_power)*drms;
+                *output_sample = F::from(output).unwrap_or_else(F::zero);
+            }
+        }
+
+        // Averaged over the channels, so the meters read the same for a mono signal on one channel or both
+        for (rms, power) in [(&self.param.input_rms, &self.input_power), (&self.param.output_rms, &self.output_power)]
+        {
+            rms.set((power.iter().sum::<f64>()/CHANNEL_COUNT as f64).sqrt() as f32);
+        }
+        self.publish_spectral_balance(band_count);
+        self.guard_cpu(start.elapsed(), samples);
+    }
+}
+
+impl Plugin for PitchShifterPlugin
+{
+    fn new(host: HostCallback) -> Self
+    where
+        Self: Sized
+    {
+        let rate = 44100.0;
+        PitchShifterPlugin {
+            host,
+            param: Arc::new(BasicFilterParameters::default()),
+            processors: Algorithm::VARIANTS.map(|algorithm| [(); BAND_COUNT_MAX].map(|()| Resampled::new(algorithm.processor(rate), rate))),
+            crossover: [(); CHANNEL_COUNT].map(|()| Self::crossover()),
+            compressor_envelope: [(); CHANNEL_COUNT].map(|()| FirstOrderFilter::new(F_COMPRESSOR_ENVELOPE*TAU)),
+            dry_delay: Self::latency_padding(rate),
+            wet_delay: Self::latency_padding(rate),
+            bypass_delay: Self::bypass_delay(rate),
+            pre_delay: Self::pre_delay(rate),
+            haas: Self::haas(rate),
+            decorrelation: [[AllPass::new(DECORRELATION_A_MAX); DECORRELATION_SPREAD.len()]; CHANNEL_COUNT],
+            limiter: Self::limiter(rate),
+            tilt: [(); CHANNEL_COUNT].map(|()| FirstOrderFilter::new(F_TILT*TAU)),
+            mix_envelope: [(); CHANNEL_COUNT].map(|()| FirstOrderFilter::new(0.0)),
+            mix_level: [0.0; CHANNEL_COUNT],
+            sidechain_envelope: [(); CHANNEL_COUNT].map(|()| FirstOrderFilter::new(0.0)),
+            sidechain_level: [0.0; CHANNEL_COUNT],
+            sidechain_fade: [1.0; CHANNEL_COUNT],
+            sidechain_connected: false,
+            input_power: [0.0; CHANNEL_COUNT],
+            output_power: [0.0; CHANNEL_COUNT],
+            pitch_detector: PitchDetector::new(),
+            routed: [(); CHANNEL_COUNT].map(|()| Vec::new()),
+            pitch_stepped: 0.0,
+            auto_tune_correction: 0.0,
+            algorithm: Algorithm::Sdft,
+            band_count: 1,
+            bypass_fade: [0.0; CHANNEL_COUNT],
+            noise_gate_open: [true; CHANNEL_COUNT],
+            noise_gate_fade: [1.0; CHANNEL_COUNT],
+            cpu_load: 0.0,
+            auto_hop: 1,
+            cpu_hold_off: 0,
+            wow_flutter_phase: [0.0; 2],
+            wow_drift: 0.0,
+            wow_drift_state: WOW_DRIFT_SEED,
+            rate
+        }
+    }
+
+    fn get_info(&self) -> Info
+    {
+        Info {
+            name: "Pitch Shifter".to_string(),
+            vendor: "Soma FX".to_string(),
+            presets: 0,
+            parameters: Control::VARIANTS.len() as i32,
+            // The extra ones are the pitch CV and the sidechain
+            inputs: CHANNEL_COUNT as i32 + 2,
+            outputs: CHANNEL_COUNT as i32,
+            midi_inputs: 1,
+            midi_outputs: 0,
+            unique_id: 976359654,
+            version: 1,
+            category: Category::Effect,
+            initial_delay: self.latency() as i32,
+            preset_chunks: true,
+            f64_precision: true,
+            silent_when_stopped: true,
+            ..Default::default()
+        }
+    }
+
+    fn set_sample_rate(&mut self, rate: f32)
+    {
+        self.rate = rate as f64;
+        self.param.sample_rate.set(rate);
+        self.pre_delay = Self::pre_delay(self.rate);
+        self.haas = Self::haas(self.rate);
+        self.limiter = Self::limiter(self.rate);
+        self.bypass_delay = Self::bypass_delay(self.rate);
+        self.dry_delay = Self::latency_padding(self.rate);
+        self.wet_delay = Self::latency_padding(self.rate);
+        for processor in self.processors.iter_mut()
+            .flatten()
+        {
+            processor.set_sample_rate(self.rate);
+        }
+    }
+
+    fn set_block_size(&mut self, size: i64)
+    {
+        for routed in self.routed.iter_mut()
+        {
+            routed.resize(size as usize, 0.0);
+        }
+        for processor in self.processors.iter_mut()
+            .flatten()
+        {
+            processor.set_block_size(size as usize);
+        }
+    }
+
+    fn get_tail_size(&self) -> isize
+    {
+        // Even with no wet in the mix the dry comes out a latency late
+        let tail = if self.param.mix.get() == 0.0 && self.param.send_mode.get() < 0.5
+        {
+            0
+        }
+        else
+        {
+            let [pre_delay, haas] = self.wet_delays();
+            // The delays hold on to the wet for that much longer
+            self.processors()
+                .iter()
+                .map(|processor| processor.tail_size())
+                .max()
+                .unwrap_or(0) + pre_delay + haas
+        };
+        // 1 tells the host there's no tail at all, 0 would mean "unknown"
+        (self.latency() + tail).max(1) as isize
+    }
+
+    fn can_do(&self, can_do: CanDo) -> Supported
+    {
+        match can_do
+        {
+            CanDo::ReceiveEvents | CanDo::ReceiveMidiEvent => Supported::Yes,
+            _ => Supported::Maybe
+        }
+    }
+
+    fn process_events(&mut self, events: &Events)
+    {
+        for event in events.events()
+        {
+            if let Event::Midi(MidiEvent {data: [status, cc, value], ..}) = event
+            {
+                if status & 0xF0 == 0xB0 && self.param.listens_to(status & 0x0F) && !self.param.learn_cc(cc)
+                {
+                    self.param.apply_cc(cc, value);
+                }
+            }
+        }
+    }
+
+    fn get_parameter_object(&mut self) -> Arc<dyn PluginParameters>
+    {
+        self.param.clone()
+    }
+
+    fn process(&mut self, buffer: &mut AudioBuffer<f32>)
+    {
+        self.process(buffer)
+    }
+
+    fn process_f64(&mut self, buffer: &mut AudioBuffer<f64>)
+    {
+        self.process(buffer)
+    }
+}
+
+plugin_main!(PitchShifterPlugin);
+#[cfg(test)]
+mod tests
+{
+    use std::f64::consts::TAU;
+    use std::time::Duration;
+
+    use vst::host::HostBuffer;
+    use vst::prelude::*;
+
+    use crate::parameters::{enum_value, Control, StereoMode, ROUTING_IDENTITY};
+    use crate::{test_signals, transfer_function, CHANNEL_COUNT, WINDOW_LENGTH};
+
+    use super::{PitchShifterPlugin, CPU_GUARD_HOLD_OFF};
+
+    const RATE: f64 = 44100.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn plugin() -> PitchShifterPlugin
+    {
+        let mut plugin = PitchShifterPlugin::new(HostCallback::default());
+        plugin.set_sample_rate(RATE as f32);
+        plugin.set_block_size(BLOCK_SIZE as i64);
+        plugin
+    }
+
+    /// Runs `inputs` through the plugin block by block, returning what comes out of each channel.
+    fn process(plugin: &mut PitchShifterPlugin, inputs: &[Vec<f64>]) -> [Vec<f64>; CHANNEL_COUNT]
+    {
+        process_in_blocks(plugin, inputs, &[BLOCK_SIZE])
+    }
+
+    /// Like [`process`], but with blocks cycling through `block_sizes`, telling the plugin whenever the size changes like a host would.
+    fn process_in_blocks(plugin: &mut PitchShifterPlugin, inputs: &[Vec<f64>], block_sizes: &[usize]) -> [Vec<f64>; CHANNEL_COUNT]
+    {
+        let length = inputs[0].len();
+        let mut outputs = [(); CHANNEL_COUNT].map(|()| vec![0.0; length]);
+        let mut host_buffer = HostBuffer::new(inputs.len(), CHANNEL_COUNT);
+        let mut start = 0;
+        for &block_size in block_sizes.iter()
+            .cycle()
+        {
+            if start >= length
+            {
+                break
+            }
+            if block_sizes.len() > 1
+            {
+                plugin.set_block_size(block_size as i64);
+            }
+            let end = (start + block_size).min(length);
+            let inputs: Vec<&[f64]> = inputs.iter()
+                .map(|x| &x[start..end])
+                .collect();
+            let mut block_outputs: Vec<&mut [f64]> = outputs.iter_mut()
+                .map(|y| &mut y[start..end])
+                .collect();
+            plugin.process_f64(&mut host_buffer.bind(&inputs, &mut block_outputs));
+            start = end;
+        }
+        outputs
+    }
+
+    fn sine(frequency: f64, length: usize) -> Vec<f64>
+    {
+        (0..length).map(|n| 0.5*(TAU*frequency*n as f64/RATE).sin())
+            .collect()
+    }
+
+    #[test]
+    fn switching_algorithm_starts_from_a_clean_state()
+    {
+        let mut plugin = plugin();
+        process(&mut plugin, &[sine(440.0, 8192), sine(440.0, 8192)]);
+
+        // Long enough for the dry and the varispeed to run out
+        plugin.param.algorithm.set(1.0);
+        process(&mut plugin, &[vec![0.0; 8192], vec![0.0; 8192]]);
+
+        // The SDFT's window still held the sine when it was switched away from
+        plugin.param.algorithm.set(0.0);
+        let outputs = process(&mut plugin, &[vec![0.0; 4096], vec![0.0; 4096]]);
+        for y in outputs.iter()
+            .flatten()
+        {
+            assert!(*y == 0.0, "{} left over from before the switch", y);
+        }
+    }
+
+    #[test]
+    fn block_size_changes_are_seamless()
+    {
+        let input = [sine(440.0, 16384), sine(660.0, 16384)];
+        let steady = process(&mut plugin(), &input);
+        let alternating = process_in_blocks(&mut plugin(), &input, &[64, 2048]);
+        for (steady, alternating) in steady.iter()
+            .flatten()
+            .zip(alternating.iter().flatten())
+        {
+            assert!((steady - alternating).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn tail_covers_the_latency_and_the_wet()
+    {
+        let plugin = plugin();
+        let latency = plugin.latency() as isize;
+        assert_eq!(plugin.get_tail_size(), latency + WINDOW_LENGTH as isize);
+
+        // Only the delayed dry is left
+        plugin.param.mix.set(0.0);
+        assert_eq!(plugin.get_tail_size(), latency);
+
+        // A send is all wet whatever the mix says
+        plugin.param.send_mode.set(1.0);
+        assert_eq!(plugin.get_tail_size(), latency + WINDOW_LENGTH as isize);
+
+        // 10 ms of pre-delay and 5 ms of Haas
+        plugin.param.pre_delay.set(10.0);
+        plugin.param.haas.set(5.0);
+        assert_eq!(plugin.get_tail_size(), latency + WINDOW_LENGTH as isize + 441 + 221);
+    }
+
+    #[test]
+    fn bypass_crossfades_without_jumping()
+    {
+        let mut plugin = plugin();
+        // A wet upside down from the input, so a hard switch would jump by twice the level
+        plugin.param.invert_wet_left.set(1.0);
+        plugin.param.invert_wet_right.set(1.0);
+        let input = sine(440.0, 3*8192);
+        let mut outputs = [(); CHANNEL_COUNT].map(|()| vec![]);
+        for (input, bypass) in input.chunks(8192)
+            .zip([0.0, 1.0, 0.0])
+        {
+            plugin.param.bypass.set(bypass);
+            for (output, y) in outputs.iter_mut()
+                .zip(process(&mut plugin, &[input.to_vec(), input.to_vec()]))
+            {
+                output.extend(y);
+            }
+        }
+        for output in outputs.iter()
+        {
+            // Past where the wet fades in from silence
+            for y in output[4096..].windows(2)
+            {
+                assert!((y[1] - y[0]).abs() < 0.1, "jumped from {} to {}", y[0], y[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn bypass_passes_the_input_through_as_it_is()
+    {
+        let mut plugin = plugin();
+        plugin.param.pitch.set(0.5);
+        plugin.param.trim_left.set(-6.0);
+        plugin.param.stereo_mode.set(enum_value(StereoMode::Swapped as usize, StereoMode::VARIANT_COUNT));
+        plugin.param.limiter.set(1.0);
+        plugin.param.bypass.set(1.0);
+        let inputs = [sine(440.0, 8192), sine(660.0, 8192)];
+        let outputs = process(&mut plugin, &inputs);
+        let latency = plugin.latency();
+        // Once the fade is done
+        for (input, output) in inputs.iter()
+            .zip(outputs.iter())
+        {
+            assert!(input[..8192 - latency].iter()
+                .zip(output[latency..].iter())
+                .skip(1024)
+                .all(|(x, y)| x == y));
+        }
+    }
+
+    #[test]
+    fn equal_band_pitch_matches_a_single_band()
+    {
+        let pitch = 5.0/12.0;
+        let mut single_band = plugin();
+        single_band.param.mix.set(1.0);
+        single_band.param.pitch.set(pitch);
+        let mut three_bands = plugin();
+        three_bands.param.mix.set(1.0);
+        three_bands.param.bands.set(1.0);
+        three_bands.param.pitch_low.set(pitch);
+        three_bands.param.pitch_mid.set(pitch);
+        three_bands.param.pitch_high.set(pitch);
+        let rms = |y: &[f64]| (y.iter().map(|y| y*y).sum::<f64>()/y.len() as f64).sqrt();
+        // On and between the crossovers, where the bands overlap the most
+        for frequency in [500.0, 1000.0, 2000.0, 4000.0, 8000.0]
+        {
+            let input = [sine(frequency, 16384), sine(frequency, 16384)];
+            let single_band = process(&mut single_band, &input);
+            let three_bands = process(&mut three_bands, &input);
+            for (single_band, three_bands) in single_band.iter()
+                .zip(three_bands.iter())
+            {
+                // The crossover only shifts the phase, so the level has to come out the same
+                let [single_band, three_bands] = [single_band, three_bands].map(|y| rms(&y[8192..]));
+                assert!((three_bands/single_band - 1.0).abs() < 0.01, "{frequency} Hz: {three_bands} against {single_band}");
+            }
+        }
+    }
+
+    #[test]
+    fn compressor_tames_a_loud_transient()
+    {
+        // Silence, then a burst well over the threshold
+        let input: Vec<f64> = sine(1000.0, 8192).into_iter()
+            .enumerate()
+            .map(|(n, x)| if n < 4096 {0.0} else {2.0*x})
+            .collect();
+        let peaks = [0.0, 1.0].map(|compressor| {
+            let mut plugin = plugin();
+            plugin.param.mix.set(1.0);
+            plugin.param.pitch.set(7.0/12.0);
+            plugin.param.ceiling.set(12.0);
+            plugin.param.compressor.set(compressor);
+            process(&mut plugin, &[input.clone(), input.clone()]).iter()
+                .flatten()
+                .fold(0.0f64, |peak, y| peak.max(y.abs()))
+        });
+        let [off, on] = peaks;
+        assert!(on < off*0.95, "peaked at {on} with the compressor against {off} without");
+    }
+
+    #[test]
+    fn wet_gain_goes_past_unity()
+    {
+        let input = [sine(440.0, 8192), sine(440.0, 8192)];
+        let outputs = [0.75, 1.0, 0.125].map(|value| {
+            let mut plugin = plugin();
+            plugin.param.mix.set(1.0);
+            plugin.param.pitch.set(7.0/12.0);
+            plugin.param.set_parameter(Control::WetGain as i32, value);
+            (plugin.param.get_parameter_text(Control::WetGain as i32), process(&mut plugin, &input))
+        });
+        let [(unity_text, unity), (over_text, over), (under_text, under)] = outputs;
+        assert_eq!([unity_text, over_text, under_text], ["100", "200", "-150"]);
+        for ((unity, over), under) in unity.iter()
+            .zip(over.iter())
+            .zip(under.iter())
+        {
+            for ((unity, over), under) in unity.iter()
+                .zip(over.iter())
+                .zip(under.iter())
+            {
+                assert!((over - 2.0*unity).abs() < 1e-9);
+                assert!((under + 1.5*unity).abs() < 1e-9);
+            }
+        }
+    }
+
+    /// Where the biggest sample of the first channel comes out, for an impulse on both channels.
+    fn impulse_peak(plugin: &mut PitchShifterPlugin) -> usize
+    {
+        let mut impulse = vec![0.0; 4*WINDOW_LENGTH];
+        impulse[0] = 1.0;
+        let [output, _] = process(plugin, &[impulse.clone(), impulse]);
+        output.iter()
+            .enumerate()
+            .fold((0, 0.0), |(peak, max), (n, y)| if y.abs() > max {(n, y.abs())} else {(peak, max)})
+            .0
+    }
+
+    #[test]
+    fn linear_phase_keeps_the_reported_latency()
+    {
+        let mut plugin = plugin();
+        plugin.param.mix.set(1.0);
+        let latency = plugin.get_info().initial_delay as usize;
+        plugin.param.phase.set(enum_value(1, 2));
+        // The symmetric filters put the peak right at the latency
+        assert_eq!(impulse_peak(&mut plugin), latency);
+        assert_eq!(plugin.get_info().initial_delay as usize, latency);
+        assert_eq!(plugin.latency(), latency);
+    }
+
+    #[test]
+    fn hann_window_keeps_the_reported_latency()
+    {
+        let mut plugin = plugin();
+        plugin.param.mix.set(1.0);
+        let latency = plugin.latency();
+        let peak = impulse_peak(&mut plugin);
+        plugin.param.hann_window.set(1.0);
+        // The analysis gets shorter, and the padding makes up for it
+        assert_eq!(plugin.latency(), latency);
+        assert_eq!(plugin.get_info().initial_delay as usize, latency);
+        assert_eq!(impulse_peak(&mut plugin), peak);
+    }
+
+    #[test]
+    fn output_is_delayed_by_the_reported_latency_at_any_mix()
+    {
+        for mix in [0.0, 0.5, 1.0]
+        {
+            let mut plugin = plugin();
+            plugin.param.mix.set(mix);
+            // So the wet has a peak that sits exactly at its delay
+            plugin.param.phase.set(enum_value(1, 2));
+            assert_eq!(impulse_peak(&mut plugin), plugin.get_info().initial_delay as usize, "at a mix of {}", mix);
+        }
+    }
+
+    #[test]
+    fn presets_are_saved_as_chunks()
+    {
+        // Otherwise the host saves the normalized values alone, and the CC bindings and routing get lost
+        assert!(plugin().get_info().preset_chunks);
+    }
+
+    #[test]
+    fn decorrelation_pulls_the_channels_apart()
+    {
+        let input = test_signals::multi_tone(RATE, &[(220.0, 0.2), (1234.5, 0.1), (3000.0, 0.1), (7000.0, 0.05)], 16384);
+        let correlations = [0.0, 0.5, 1.0].map(|amount| {
+            let mut plugin = plugin();
+            plugin.param.mix.set(1.0);
+            plugin.param.pitch.set(7.0/12.0);
+            plugin.param.decorrelation.set(amount);
+            let [left, right] = process(&mut plugin, &[input.clone(), input.clone()]);
+            let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a*b).sum::<f64>();
+            let (left, right) = (&left[8192..], &right[8192..]);
+            dot(left, right)/(dot(left, left)*dot(right, right)).sqrt()
+        });
+        assert_eq!(correlations[0], 1.0);
+        assert!(correlations[1] < correlations[0] && correlations[2] < correlations[1], "{:?}", correlations);
+    }
+
+    #[test]
+    fn dry_and_wet_line_up_at_unity_pitch()
+    {
+        let mut plugin = plugin();
+        plugin.param.mix.set(0.5);
+        plugin.param.phase.set(enum_value(1, 2));
+        let mut impulse = vec![0.0; 4*WINDOW_LENGTH];
+        impulse[0] = 1.0;
+        let [impulse_response, _] = process(&mut plugin, &[impulse.clone(), impulse]);
+        // Any misalignment would comb the two, and any phase left over would smear
+        let deviation = transfer_function::deviation_from_delay(RATE, &impulse_response, plugin.latency(), &[700.0, 1000.0, 2000.0, 4000.0, 6000.0]);
+        assert!(deviation.magnitude < 0.1 && deviation.phase < 1e-6, "{:?}", deviation);
+    }
+
+    #[test]
+    fn limiter_holds_the_ceiling()
+    {
+        let mut plugin = plugin();
+        let latency = plugin.get_info().initial_delay;
+        plugin.param.limiter.set(1.0);
+        plugin.param.mix.set(0.5);
+        plugin.param.pitch.set(7.0/12.0);
+        // Well over full scale, with a jump straight to it halfway through
+        let input: Vec<f64> = sine(440.0, 16384).into_iter()
+            .enumerate()
+            .map(|(n, x)| if n < 8192 {x} else {8.0*x})
+            .collect();
+        let outputs = process(&mut plugin, &[input.clone(), input]);
+        assert!(outputs.iter().flatten().all(|y| y.abs() <= 1.0));
+        assert_eq!(plugin.get_info().initial_delay, latency);
+        assert_eq!(plugin.latency() as i32, latency);
+    }
+
+    #[test]
+    fn auto_tune_snaps_the_shifted_pitch()
+    {
+        let semitone = 1.0/12.0;
+        // A slightly flat A goes up to the A
+        let flat = 440.0*2.0f64.powf(-0.3*semitone);
+        assert!((PitchShifterPlugin::auto_tune_target(flat, 0.0, 1.0) - 0.3*semitone).abs() < 1e-9);
+        // An A shifted off the scale is pulled back onto it, not left where the shift put it
+        assert!((PitchShifterPlugin::auto_tune_target(440.0, 5.4*semitone, 1.0) + 0.4*semitone).abs() < 1e-9);
+        assert!((PitchShifterPlugin::auto_tune_target(440.0, 5.4*semitone, 0.5) + 0.2*semitone).abs() < 1e-9);
+        assert_eq!(PitchShifterPlugin::auto_tune_target(flat, 5.0*semitone, 0.0), 0.0);
+    }
+
+    #[test]
+    fn tuner_hears_nothing_without_inputs()
+    {
+        let mut plugin = plugin();
+        plugin.param.tuner.set(1.0);
+        plugin.param.auto_tune.set(1.0);
+        let mut host_buffer = HostBuffer::<f64>::new(0, CHANNEL_COUNT);
+        let mut outputs = [(); CHANNEL_COUNT].map(|()| vec![0.0; BLOCK_SIZE]);
+        let mut outputs: Vec<&mut [f64]> = outputs.iter_mut()
+            .map(|y| &mut y[..])
+            .collect();
+        let inputs: [&[f64]; 0] = [];
+        let mut buffer = host_buffer.bind(&inputs, &mut outputs);
+        assert_eq!(plugin.detect_pitch(&mut buffer), None);
+        assert_eq!(plugin.param.detected_pitch.get(), 0.0);
+        // And the rest of the block gets through it too
+        plugin.process_f64(&mut buffer);
+    }
+
+    #[test]
+    fn routing_swaps_and_sums_the_channels()
+    {
+        let input = [sine(440.0, 8192), vec![0.0; 8192]];
+        let run = |input_routing, output_routing| {
+            let mut plugin = plugin();
+            plugin.param.mix.set(0.5);
+            plugin.param.pitch.set(5.0/12.0);
+            plugin.param.set_routing(input_routing, output_routing);
+            process(&mut plugin, &input)
+        };
+        let [left, right] = run(ROUTING_IDENTITY, ROUTING_IDENTITY);
+        assert!(right.iter().all(|&y| y == 0.0));
+        assert!(left.iter().any(|&y| y != 0.0));
+
+        let [swapped_left, swapped_right] = run(ROUTING_IDENTITY, [[0.0, 1.0], [1.0, 0.0]]);
+        assert_eq!(swapped_left, right);
+        assert_eq!(swapped_right, left);
+
+        // Both get half of the left, and the shifter is linear with the limiter off
+        let [mono_left, mono_right] = run([[0.5, 0.5], [0.5, 0.5]], ROUTING_IDENTITY);
+        assert_eq!(mono_left, mono_right);
+        for (y, y_mono) in left.iter()
+            .zip(mono_left.iter())
+        {
+            assert!((y*0.5 - y_mono).abs() < 1e-9, "{} came out as {}", y*0.5, y_mono);
+        }
+    }
+
+    #[test]
+    fn stereo_modes_route_as_documented()
+    {
+        let input = [sine(440.0, 8192), sine(1000.0, 8192)];
+        let run = |mode: StereoMode| {
+            let mut plugin = plugin();
+            plugin.param.mix.set(0.5);
+            plugin.param.pitch.set(5.0/12.0);
+            plugin.param.stereo_mode.set(enum_value(mode as usize, StereoMode::VARIANT_COUNT));
+            process(&mut plugin, &input)
+        };
+        // Every channel is shifted alike and the limiter is off, so each mode is a mix of what plain stereo puts out
+        let [left, right] = run(StereoMode::Stereo);
+        for mode in StereoMode::VARIANTS
+        {
+            let [[ll, lr], [rl, rr]] = match mode
+            {
+                StereoMode::Stereo | StereoMode::MidSide => [[1.0, 0.0], [0.0, 1.0]],
+                StereoMode::MonoSum => [[0.5, 0.5], [0.5, 0.5]],
+                StereoMode::Swapped => [[0.0, 1.0], [1.0, 0.0]],
+                StereoMode::Wide => [[1.5, -0.5], [-0.5, 1.5]]
+            };
+            let [mode_left, mode_right] = run(mode);
+            for (n, ((l, r), (mode_left, mode_right))) in left.iter()
+                .zip(right.iter())
+                .zip(mode_left.iter().zip(mode_right.iter()))
+                .enumerate()
+            {
+                assert!((l*ll + r*lr - mode_left).abs() < 1e-9, "left of {} at sample {}", StereoMode::NAMES[mode as usize], n);
+                assert!((l*rl + r*rr - mode_right).abs() < 1e-9, "right of {} at sample {}", StereoMode::NAMES[mode as usize], n);
+            }
+        }
+    }
+
+    #[test]
+    fn sidechain_gates_the_wet()
+    {
+        // Two pulses, each loud for a quarter second and then silent for a quarter second
+        let period = RATE as usize/2;
+        let length = 2*period;
+        let input = sine(440.0, length);
+        let pulses: Vec<f64> = (0..length).map(|n| if n % period < period/2 {0.5} else {0.0})
+            .collect();
+        let run = |sidechain: &[f64]| {
+            let mut plugin = plugin();
+            plugin.param.mix.set(1.0);
+            plugin.param.sidechain_gate.set(1.0);
+            plugin.param.sidechain_threshold.set(-20.0);
+            plugin.param.sidechain_release.set(10.0);
+            let [left, _] = process(&mut plugin, &[input.clone(), input.clone(), vec![0.0; length], sidechain.to_vec()]);
+            // The gate acts on the output as it leaves, so compare around the middle of each half of the second pulse, well past
+            // the latency
+            [period + period/4, period + 3*period/4].map(|centre| {
+                let window = &left[centre - 1024..centre + 1024];
+                (window.iter()
+                    .map(|y| y*y)
+                    .sum::<f64>()/window.len() as f64).sqrt()
+            })
+        };
+        let [open, closed] = run(&pulses);
+        assert!(open > 0.2 && closed < open*0.01, "{} while the sidechain is loud against {} while it's silent", open, closed);
+
+        // Declared but silent, like a host with nothing routed to it
+        let [first, second] = run(&vec![0.0; length]);
+        assert!(first > 0.2 && second > 0.2, "silent sidechain gated the wet to {} and {}", first, second);
+    }
+
+    #[test]
+    fn match_gain_brings_the_wet_to_the_input_level()
+    {
+        let input = sine(440.0, 2*RATE as usize);
+        let mut plugin = plugin();
+        plugin.param.mix.set(1.0);
+        plugin.param.pitch.set(7.0/12.0);
+        plugin.param.wet_gain.set(0.5);
+        let output = process(&mut plugin, &[input.clone(), input.clone()]);
+        let match_gain = plugin.param.match_gain() as f64;
+        let rms = |x: &[f64]| (x.iter()
+            .map(|x| x*x)
+            .sum::<f64>()/x.len() as f64).sqrt();
+        // The last half second, long settled
+        let tail = input.len() - RATE as usize/2;
+        let input_rms = rms(&input[tail..]);
+        for output in output.iter()
+        {
+            let matched = rms(&output[tail..])*match_gain;
+            assert!((matched/input_rms - 1.0).abs() < 0.05, "matched to {} against an input of {}", matched, input_rms);
+        }
+    }
+
+    #[test]
+    fn cpu_guard_degrades_under_load_and_keeps_the_latency()
+    {
+        let mut loaded = plugin();
+        loaded.param.cpu_guard.set(1.0);
+        let latency = loaded.get_info().initial_delay;
+        let block = Duration::from_secs_f64(BLOCK_SIZE as f64/RATE);
+        let blocks_per_second = RATE as usize/BLOCK_SIZE;
+
+        // Nearly the whole block spent processing it
+        for _ in 0..blocks_per_second
+        {
+            loaded.guard_cpu(block.mul_f64(0.95), BLOCK_SIZE);
+        }
+        assert!(loaded.auto_hop > 1);
+        assert_eq!(loaded.param.effective_hop.get() as usize, loaded.auto_hop);
+        assert_eq!(loaded.get_info().initial_delay, latency);
+        assert_eq!(loaded.latency() as i32, latency);
+        // The longer hop is padded out, so the wet still lines up with the reported latency
+        loaded.param.mix.set(1.0);
+        loaded.param.phase.set(enum_value(1, 2));
+        assert_eq!(impulse_peak(&mut loaded), latency as usize);
+
+        // A load swinging across both thresholds only moves the hop once per hold-off
+        let mut swinging = plugin();
+        swinging.param.cpu_guard.set(1.0);
+        let mut changes = 0;
+        let mut hop = swinging.auto_hop;
+        for n in 0..blocks_per_second
+        {
+            swinging.guard_cpu(block.mul_f64(if n/4%2 == 0 {2.0} else {0.0}), BLOCK_SIZE);
+            if swinging.auto_hop != hop
+            {
+                changes += 1;
+                hop = swinging.auto_hop;
+            }
+        }
+        assert!(changes <= 1 + (1.0/CPU_GUARD_HOLD_OFF) as usize, "the hop changed {} times in a second", changes);
+    }
+}
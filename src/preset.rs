@@ -0,0 +1,260 @@
+//! The preset format behind [`get_preset_data`](vst::prelude::PluginParameters::get_preset_data).
+//!
+//! A preset starts with [`MAGIC`] and a version byte, followed by records of a tag byte, a little-endian `u16` payload length and
+//! the payload. Controls are stored under their [`key`](Control::key) rather than their index, so presets survive controls being
+//! added or reordered. Records with tags or keys this version doesn't know are skipped, so older builds can load newer presets.
+//!
+//! Data without the magic is taken to be the original positional format, from before there were any presets beyond pitch, fine
+//! pitch and mix: those three normalized values as little-endian `f32`s in that order, and nothing else.
+//!
+//! Values outside 0.0 to 1.0 are clamped, and values that aren't a number are left out, so one bad value doesn't lose the rest.
+
+use std::sync::atomic::Ordering;
+
+use vst::prelude::PluginParameters;
+
+use crate::parameters::{BasicFilterParameters, Control};
+use crate::{PitchShifterError, CHANNEL_COUNT};
+
+pub const MAGIC: [u8; 4] = *b"PSHP";
+pub const VERSION: u8 = 1;
+
+/// Normalized value of a control. The key, then the value as an `f32`.
+const TAG_VALUE: u8 = 1;
+/// MIDI CC binding of a control. The key, then the CC number.
+const TAG_MIDI_CC: u8 = 2;
+/// One routing gain. 0 for input or 1 for output, the row, the column, then the gain as an `f32`.
+const TAG_ROUTING: u8 = 3;
+
+/// Controls of the positional format, in the order it stores them.
+const LEGACY_CONTROLS: [Control; 3] = [Control::Pitch, Control::PitchFine, Control::Mix];
+
+/// One change a preset makes, checked before any of them are applied.
+enum Record
+{
+    Value(Control, f32),
+    MidiCc(Control, u8),
+    Routing(usize, usize, usize, f32)
+}
+
+fn record(data: &mut Vec<u8>, tag: u8, payload: &[u8])
+{
+    data.push(tag);
+    data.extend((payload.len() as u16).to_le_bytes());
+    data.extend_from_slice(payload);
+}
+
+fn keyed(control: Control, value: &[u8]) -> Vec<u8>
+{
+    let key = control.key().as_bytes();
+    let mut payload = Vec::with_capacity(1 + key.len() + value.len());
+    payload.push(key.len() as u8);
+    payload.extend_from_slice(key);
+    payload.extend_from_slice(value);
+    payload
+}
+
+/// Splits a keyed payload into its control and the rest. `None` for a control this version doesn't have.
+fn unkeyed(payload: &[u8]) -> Result<Option<(Control, &[u8])>, ()>
+{
+    let (&length, payload) = payload.split_first().ok_or(())?;
+    let key = payload.get(..length as usize).ok_or(())?;
+    let key = std::str::from_utf8(key).map_err(|_| ())?;
+    Ok(Control::from_key(key).map(|control| (control, &payload[length as usize..])))
+}
+
+fn value(control: Control, value: f32) -> Option<Record>
+{
+    if value.is_nan()
+    {
+        return None
+    }
+    Some(Record::Value(control, value.clamp(0.0, 1.0)))
+}
+
+pub fn encode(p: &BasicFilterParameters) -> Vec<u8>
+{
+    let mut data = MAGIC.to_vec();
+    data.push(VERSION);
+    for v in Control::VARIANTS
+    {
+        record(&mut data, TAG_VALUE, &keyed(v, &p.get_parameter(v as i32).to_le_bytes()));
+    }
+    for (v, midi_cc) in Control::VARIANTS.into_iter()
+        .zip(p.midi_cc.iter())
+    {
+        record(&mut data, TAG_MIDI_CC, &keyed(v, &[midi_cc.load(Ordering::Relaxed)]));
+    }
+    for (matrix, routing) in p.routing().into_iter()
+        .enumerate()
+    {
+        for (i, row) in routing.into_iter()
+            .enumerate()
+        {
+            for (j, gain) in row.into_iter()
+                .enumerate()
+            {
+                let mut payload = vec![matrix as u8, i as u8, j as u8];
+                payload.extend(gain.to_le_bytes());
+                record(&mut data, TAG_ROUTING, &payload);
+            }
+        }
+    }
+    data
+}
+
+fn decode_records(data: &[u8]) -> Result<Vec<Record>, PitchShifterError>
+{
+    let invalid = PitchShifterError::InvalidPreset {length: data.len()};
+    let mut records = vec![];
+    // Any version can be read, since the records describe themselves
+    let mut rest = &data[MAGIC.len() + 1..];
+    while let Some((&tag, tail)) = rest.split_first()
+    {
+        let length = tail.get(..2).ok_or(invalid)?;
+        let length = u16::from_le_bytes([length[0], length[1]]) as usize;
+        let payload = tail.get(2..2 + length).ok_or(invalid)?;
+        rest = &tail[2 + length..];
+
+        match tag
+        {
+            TAG_VALUE => if let Some((control, b)) = unkeyed(payload).map_err(|()| invalid)?
+            {
+                let b = b.try_into().map_err(|_| invalid)?;
+                records.extend(value(control, f32::from_le_bytes(b)));
+            },
+            TAG_MIDI_CC => if let Some((control, b)) = unkeyed(payload).map_err(|()| invalid)?
+            {
+                let &[cc] = b else {return Err(invalid)};
+                records.push(Record::MidiCc(control, cc));
+            },
+            TAG_ROUTING => {
+                let &[matrix, i, j, b0, b1, b2, b3] = payload else {return Err(invalid)};
+                let (matrix, i, j) = (matrix as usize, i as usize, j as usize);
+                // Routing for channels this build doesn't have has nowhere to go
+                if matrix < 2 && i < CHANNEL_COUNT && j < CHANNEL_COUNT
+                {
+                    records.push(Record::Routing(matrix, i, j, f32::from_le_bytes([b0, b1, b2, b3])));
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the positional format.
+fn decode_legacy(data: &[u8]) -> Result<Vec<Record>, PitchShifterError>
+{
+    if data.len() != LEGACY_CONTROLS.len()*4
+    {
+        return Err(PitchShifterError::InvalidPreset {length: data.len()})
+    }
+    Ok(LEGACY_CONTROLS.into_iter()
+        .zip(data.chunks_exact(4))
+        .filter_map(|(v, b)| value(v, f32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+        .collect())
+}
+
+pub fn decode(p: &BasicFilterParameters, data: &[u8]) -> Result<(), PitchShifterError>
+{
+    let records = if data.len() > MAGIC.len() && data[..MAGIC.len()] == MAGIC
+    {
+        decode_records(data)?
+    }
+    else
+    {
+        decode_legacy(data)?
+    };
+
+    for record in records
+    {
+        match record
+        {
+            // Loading isn't the user touching anything, as far as MIDI learn goes
+            Record::Value(control, value) => p.set_value(control as i32, value),
+            Record::MidiCc(control, cc) => p.midi_cc[control as usize].store(cc, Ordering::Relaxed),
+            Record::Routing(matrix, i, j, gain) => [&p.input_routing, &p.output_routing][matrix][i][j].set(gain)
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::atomic::Ordering;
+
+    use vst::prelude::PluginParameters;
+
+    use crate::parameters::{BasicFilterParameters, Control, NO_CC};
+
+    use super::{decode, encode};
+
+    #[test]
+    fn midi_cc_bindings_round_trip()
+    {
+        let p = BasicFilterParameters::default();
+        p.midi_cc[Control::Mix as usize].store(21, Ordering::Relaxed);
+        p.midi_cc[Control::Pitch as usize].store(22, Ordering::Relaxed);
+
+        let q = BasicFilterParameters::default();
+        decode(&q, &encode(&p)).unwrap();
+        for v in Control::VARIANTS
+        {
+            let cc = match v
+            {
+                Control::Mix => 21,
+                Control::Pitch => 22,
+                _ => NO_CC
+            };
+            assert_eq!(q.midi_cc[v as usize].load(Ordering::Relaxed), cc, "{}", v.key());
+        }
+    }
+
+    #[test]
+    fn every_control_round_trips()
+    {
+        let p = BasicFilterParameters::default();
+        for v in Control::VARIANTS
+        {
+            p.set_parameter(v as i32, (v as usize*37%101) as f32/100.0);
+            p.midi_cc[v as usize].store(v as u8, Ordering::Relaxed);
+        }
+        p.set_routing([[0.0, 1.0], [1.0, 0.0]], [[0.5, 0.5], [0.25, -0.75]]);
+
+        let q = BasicFilterParameters::default();
+        decode(&q, &encode(&p)).unwrap();
+        for v in Control::VARIANTS
+        {
+            let (expected, value) = (p.get_parameter(v as i32), q.get_parameter(v as i32));
+            assert!((expected - value).abs() < 1e-6, "{} is at {} instead of {}", v.key(), value, expected);
+            assert_eq!(q.midi_cc[v as usize].load(Ordering::Relaxed), v as u8, "{}", v.key());
+        }
+        assert_eq!(q.routing(), p.routing());
+        // Loading isn't touching
+        assert_eq!(q.touched.load(Ordering::Relaxed), -1);
+    }
+
+    #[test]
+    fn legacy_presets_load()
+    {
+        let legacy = |values: [f32; 3]| values.map(f32::to_le_bytes).concat();
+        let p = BasicFilterParameters::default();
+        decode(&p, &legacy([0.75, 0.5, 0.25])).unwrap();
+        assert_eq!(p.get_parameter(Control::Pitch as i32), 0.75);
+        assert_eq!(p.get_parameter(Control::PitchFine as i32), 0.5);
+        assert_eq!(p.get_parameter(Control::Mix as i32), 0.25);
+        assert_eq!(p.touched.load(Ordering::Relaxed), -1);
+
+        // Out of range is clamped, and what isn't a number is left as it was
+        decode(&p, &legacy([f32::NAN, 1.5, -0.5])).unwrap();
+        assert_eq!(p.get_parameter(Control::Pitch as i32), 0.75);
+        assert_eq!(p.get_parameter(Control::PitchFine as i32), 1.0);
+        assert_eq!(p.get_parameter(Control::Mix as i32), 0.0);
+
+        // Nothing else was ever stored positionally
+        assert!(decode(&p, &[legacy([0.0; 3]), vec![0; 4]].concat()).is_err());
+        assert!(decode(&p, &legacy([0.0; 3])[..8]).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::pitch_shifter::{self, PitchShifter};
+use crate::varispeed::Varispeed;
+use crate::CHANNEL_COUNT;
+
+/// Options shared by all algorithms. Algorithms ignore the ones that don't apply to them.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Settings
+{
+    /// Band-limit with linear-phase FIR filters instead of minimum-phase IIR filters, at the cost of latency.
+    pub linear_phase: bool,
+    pub aliasing: Quality,
+    /// High-pass the resynthesized output just above DC, so DC in the analysis window can't wander around in the wet.
+    pub dc_block: bool,
+    /// Scale the resynthesis to the energy of the analysed input, keeping the level steady when the resynthesis gains or loses energy.
+    pub normalize: bool,
+    /// Leave out bins of the analysis spectrum quieter than this, as an amplitude relative to a full scale sine. Zero turns the gate off.
+    ///
+    /// What's left open is still read between samples without beating, but a high threshold closes off the leakage that makes up the
+    /// edges of the window. The resynthesis reads close to an edge at small shifts, so there the gate itself swells and dips the level
+    /// as the phase crawls along.
+    pub gate_threshold: f64,
+    /// Time constant in seconds for the gate on each bin to open and close, so it doesn't flutter.
+    pub gate_smoothing: f64,
+    /// Resynthesize without any phase rotation, for hearing what the analysis and resynthesis do on their own. For diagnostics.
+    pub resynthesis_only: bool,
+    /// Only resynthesize every this many samples, interpolating in between. Saves CPU at the cost of top end and `hop - 1` samples of latency.
+    /// 0 and 1 both resynthesize every sample.
+    pub hop: usize,
+    /// How far up the anti-alias floors cut the low end, in bins of the analysis window. Lower keeps more low end, but lets through
+    /// content the window is too short to resolve. Zero uses [`LOW_CUT_DEFAULT`](pitch_shifter::LOW_CUT_DEFAULT).
+    pub low_cut: f64,
+    pub dc_bin: DcBin,
+    /// Time constant in seconds for the anti-alias cutoffs to follow a pitch change. Zero uses
+    /// [`CUTOFF_GLIDE_TIME`](pitch_shifter::CUTOFF_GLIDE_TIME).
+    pub cutoff_glide: f64,
+    /// Hann window the analysis and resynthesize from two overlapping heads, for less leakage and a less metallic tone. Halves the
+    /// latency of the analysis window.
+    ///
+    /// The heads are read between samples the same way as without it. At small shifts they crossfade slowly though, which swells and
+    /// dips the level by as much as they're out of phase.
+    pub hann: bool
+}
+
+/// How hard the anti-alias band-limiting works.
+///
+/// There's no oversampling, so each step up cascades another third order stage on the anti-alias ceilings instead.
+/// That's a steeper rolloff for a bit more CPU and phase shift near the ceilings, but no added latency.
+/// In linear-phase mode the FIR filters do the band-limiting and this has no effect.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quality
+{
+    /// One stage, 18 dB/octave.
+    #[default]
+    Low,
+    /// Two stages, 36 dB/octave.
+    Medium,
+    /// Three stages, 54 dB/octave.
+    High
+}
+
+// Spelled out rather than left to the nightly-only variant_count, and checked against it with the nightly feature
+#[cfg(feature = "nightly")]
+const _: () = assert!(core::mem::variant_count::<Quality>() == Quality::VARIANT_COUNT);
+
+impl Quality
+{
+    pub const VARIANT_COUNT: usize = Self::VARIANTS.len();
+    pub const VARIANTS: [Self; 3] = [
+        Self::Low,
+        Self::Medium,
+        Self::High
+    ];
+    pub const NAMES: [&'static str; Self::VARIANT_COUNT] = [
+        "Low",
+        "Medium",
+        "High"
+    ];
+
+    /// Number of cascaded filter stages on each anti-alias ceiling.
+    pub fn stages(self) -> usize
+    {
+        self as usize + 1
+    }
+}
+
+/// What the resynthesis does with the DC bin of the analysis, which the phase rotation leaves alone since it has no frequency to shift.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum DcBin
+{
+    /// Added back as it is. Any DC or very low rumble in the window comes through unshifted.
+    #[default]
+    Pass,
+    /// Rotated along with the first bin, so DC comes out as a low tone at the rate the phase turns. Heard as a wobble when the input
+    /// has a lot of DC.
+    Rotate,
+    /// Left out, like a steep high-pass at the first bin.
+    Remove
+}
+
+// Spelled out rather than left to the nightly-only variant_count, and checked against it with the nightly feature
+#[cfg(feature = "nightly")]
+const _: () = assert!(core::mem::variant_count::<DcBin>() == DcBin::VARIANT_COUNT);
+
+impl DcBin
+{
+    pub const VARIANT_COUNT: usize = Self::VARIANTS.len();
+    pub const VARIANTS: [Self; 3] = [
+        Self::Pass,
+        Self::Rotate,
+        Self::Remove
+    ];
+    pub const NAMES: [&'static str; Self::VARIANT_COUNT] = [
+        "Pass",
+        "Rotate",
+        "Remove"
+    ];
+}
+
+/// A pitch shifting algorithm, processing each channel one sample at a time.
+pub trait AudioProcessor: Send
+{
+    fn set_sample_rate(&mut self, rate: f64);
+
+    /// Called outside the audio thread when the host changes its maximum block size, so any scratch can be reallocated.
+    fn set_block_size(&mut self, _size: usize) {}
+
+    fn configure(&mut self, settings: &Settings);
+
+    /// Processing latency in samples with the current settings.
+    fn latency(&self) -> usize;
+
+    /// Sets the pitch shift in octaves.
+    fn set_pitch(&mut self, octaves: f64);
+
+    /// Offsets the pitch by `octaves` on top of [`set_pitch`](AudioProcessor::set_pitch), cheap enough to call every sample.
+    ///
+    /// Only the shift follows it, anything tuned to the pitch stays where `set_pitch` put it.
+    fn set_pitch_modulation(&mut self, octaves: f64);
+
+    fn set_pitch_semitones(&mut self, semitones: f64)
+    {
+        self.set_pitch(semitones/12.0)
+    }
+
+    fn set_pitch_cents(&mut self, cents: f64)
+    {
+        self.set_pitch(cents/1200.0)
+    }
+
+    /// Sets the pitch shift as a frequency ratio, like 2.0 for an octave up.
+    fn set_pitch_ratio(&mut self, ratio: f64)
+    {
+        self.set_pitch(ratio.log2())
+    }
+
+    /// How many samples of output the processor keeps producing after the input goes silent.
+    fn tail_size(&self) -> usize;
+
+    /// Clears all internal state, as if the processor was just created.
+    fn reset(&mut self);
+
+    /// Pulls every channel's phase onto the first channel's, so the same input on every channel comes out the same.
+    ///
+    /// Meant to be called between blocks, when every channel has been processed equally far.
+    fn link_channels(&mut self) {}
+
+    /// Mean square level of a channel's analysed input and of the wet it puts out, in the low, mid and high bands, as metered by a
+    /// [`BandMeter`](crate::crossover::BandMeter) split at [`BALANCE_CROSSOVERS`](pitch_shifter::BALANCE_CROSSOVERS). `None` for
+    /// processors that don't meter it.
+    fn spectral_balance(&self, _channel: usize) -> Option<[[f64; 3]; 2]>
+    {
+        None
+    }
+
+    /// Processes one sample of a channel at the given sample rate.
+    ///
+    /// If `rate` differs from the rate the processor is currently running at, the processor switches to it and
+    /// [`reset`](AudioProcessor::reset)s, since its internal state no longer makes sense at the new rate.
+    fn process_sample(&mut self, rate: f64, channel: usize, x: f64) -> f64;
+
+    /// Feeds a sample to a channel's analysis without needing its output, so it's ready to resynthesize once the output is needed
+    /// again. Processors that can skip their resynthesis save the CPU, the rest just throw the output away.
+    fn analyse_sample(&mut self, rate: f64, channel: usize, x: f64)
+    {
+        self.process_sample(rate, channel, x);
+    }
+
+    /// Processes a block of a channel in place. Same rate semantics as [`process_sample`](AudioProcessor::process_sample).
+    fn process_block(&mut self, rate: f64, channel: usize, block: &mut [f64])
+    {
+        for x in block.iter_mut()
+        {
+            *x = self.process_sample(rate, channel, *x);
+        }
+    }
+
+    /// Feeds a unit impulse through a channel from a clean state, and returns the first `length` samples that come out.
+    ///
+    /// Leaves the processor [`reset`](AudioProcessor::reset) afterwards, so the impulse doesn't ring into whatever comes next.
+    fn impulse_response(&mut self, rate: f64, channel: usize, length: usize) -> Vec<f64>
+    {
+        self.reset();
+        let mut response = vec![0.0; length];
+        if let Some(x) = response.first_mut()
+        {
+            *x = 1.0;
+        }
+        self.process_block(rate, channel, &mut response);
+        self.reset();
+        response
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm
+{
+    Sdft,
+    /// Plays the input back faster or slower like a tape, see [`Varispeed`].
+    Varispeed
+}
+
+// Spelled out rather than left to the nightly-only variant_count, and checked against it with the nightly feature
+#[cfg(feature = "nightly")]
+const _: () = assert!(core::mem::variant_count::<Algorithm>() == Algorithm::VARIANT_COUNT);
+
+impl Algorithm
+{
+    pub const VARIANT_COUNT: usize = Self::VARIANTS.len();
+    pub const VARIANTS: [Self; 2] = [
+        Self::Sdft,
+        Self::Varispeed
+    ];
+    pub const NAMES: [&'static str; Self::VARIANT_COUNT] = [
+        "SDFT",
+        "Varispeed"
+    ];
+
+    /// Picks the algorithm from a normalized parameter value between 0.0 and 1.0.
+    pub fn from(value: f32) -> Self
+    {
+        let i = (value*(Self::VARIANT_COUNT - 1) as f32).round() as usize;
+        Self::VARIANTS[i.min(Self::VARIANT_COUNT - 1)]
+    }
+
+    pub fn name(self) -> &'static str
+    {
+        Self::NAMES[self as usize]
+    }
+
+    /// Upper bound on the latency the algorithm can report, with any settings.
+    pub fn max_latency(self) -> usize
+    {
+        match self
+        {
+            Algorithm::Sdft => pitch_shifter::MAX_LATENCY,
+            Algorithm::Varispeed => 0
+        }
+    }
+
+    pub fn processor(self, rate: f64) -> Box<dyn AudioProcessor>
+    {
+        match self
+        {
+            Algorithm::Sdft => Box::new(PitchShifter::<CHANNEL_COUNT>::new(rate)),
+            Algorithm::Varispeed => Box::new(Varispeed::<CHANNEL_COUNT>::new(rate))
+        }
+    }
+}
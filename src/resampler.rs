@@ -0,0 +1,417 @@
+use std::f64::consts::PI;
+
+use crate::{processor::{AudioProcessor, Settings}, CHANNEL_COUNT};
+
+/// Half the length of the resampling kernels, in periods of the lower of the host and internal rates.
+const HALF_TAPS: usize = 16;
+
+/// How much of the lower rate's band the kernels pass, leaving the rest as the transition band.
+const ROLLOFF: f64 = 0.9;
+
+/// Points per period in the table the kernel is read from.
+const KERNEL_RESOLUTION: usize = 256;
+
+/// Lowest and highest host rates the rings are allocated for up front, so a rate change between them doesn't allocate.
+const HOST_RATE_RANGE: [f64; 2] = [8000.0, 384000.0];
+
+/// The rate a processor runs at inside the plugin, resampled to and from the host's.
+///
+/// The analysis window is a number of samples, so at a fixed rate it spans the same time and the wet sounds the same in any
+/// session. Costs the resampling, and the latency of its kernels on top of the processor's.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum InternalRate
+{
+    /// Whatever rate the host runs at, without resampling.
+    #[default]
+    Host,
+    Rate44100,
+    Rate48000,
+    Rate96000
+}
+
+// Spelled out rather than left to the nightly-only variant_count, and checked against it with the nightly feature
+#[cfg(feature = "nightly")]
+const _: () = assert!(core::mem::variant_count::<InternalRate>() == InternalRate::VARIANT_COUNT);
+
+impl InternalRate
+{
+    pub const VARIANT_COUNT: usize = Self::VARIANTS.len();
+    pub const VARIANTS: [Self; 4] = [
+        Self::Host,
+        Self::Rate44100,
+        Self::Rate48000,
+        Self::Rate96000
+    ];
+    pub const NAMES: [&'static str; Self::VARIANT_COUNT] = [
+        "Host",
+        "44.1 kHz",
+        "48 kHz",
+        "96 kHz"
+    ];
+
+    /// The fixed rate, or `None` to run at the host's.
+    pub fn rate(self) -> Option<f64>
+    {
+        match self
+        {
+            Self::Host => None,
+            Self::Rate44100 => Some(44100.0),
+            Self::Rate48000 => Some(48000.0),
+            Self::Rate96000 => Some(96000.0)
+        }
+    }
+}
+
+/// Hann windowed sinc, tabulated from the middle out over [`HALF_TAPS`] periods of the lower rate.
+fn kernel_table() -> Vec<f64>
+{
+    (0..=HALF_TAPS*KERNEL_RESOLUTION + 1).map(|i| {
+            let u = i as f64/KERNEL_RESOLUTION as f64;
+            let window = if u < HALF_TAPS as f64 {0.5 + 0.5*(PI*u/HALF_TAPS as f64).cos()} else {0.0};
+            let x = PI*ROLLOFF*u;
+            let sinc = if x == 0.0 {1.0} else {x.sin()/x};
+            sinc*window
+        }).collect()
+}
+
+/// Runs a processor at an [`InternalRate`] of its own, resampling each channel's input to it and the output back.
+///
+/// With [`InternalRate::Host`] it's passed straight through.
+pub struct Resampled<const C: usize = CHANNEL_COUNT>
+{
+    processor: Box<dyn AudioProcessor>,
+    internal_rate: InternalRate,
+    kernel: Vec<f64>,
+    /// The latest host input on each channel, as a ring indexed by sample count.
+    input: [Vec<f64>; C],
+    /// The latest processed output on each channel, at the internal rate, as a ring indexed by sample count.
+    output: [Vec<f64>; C],
+    /// Host samples taken in and internal samples processed on each channel.
+    count: [(u64, u64); C],
+    rate: f64
+}
+
+impl<const C: usize> Resampled<C>
+{
+    pub fn new(processor: Box<dyn AudioProcessor>, rate: f64) -> Self
+    {
+        // The rings only get longer toward either end of the range
+        let capacity = HOST_RATE_RANGE.into_iter()
+            .chain([rate])
+            .map(Self::ring_lengths)
+            .fold([0, 0], |[input, output], [i, o]| [input.max(i), output.max(o)]);
+        let mut resampled = Self {
+            processor,
+            internal_rate: InternalRate::Host,
+            kernel: kernel_table(),
+            input: [(); C].map(|()| Vec::with_capacity(capacity[0])),
+            output: [(); C].map(|()| Vec::with_capacity(capacity[1])),
+            count: [(0, 0); C],
+            rate
+        };
+        resampled.resize_rings();
+        resampled
+    }
+
+    /// Clears the rings to the lengths the current rate needs, within what was allocated for [`HOST_RATE_RANGE`].
+    fn resize_rings(&mut self)
+    {
+        let lengths = Self::ring_lengths(self.rate);
+        for (rings, length) in [&mut self.input, &mut self.output].into_iter()
+            .zip(lengths)
+        {
+            for ring in rings.iter_mut()
+            {
+                ring.clear();
+                ring.resize(length, 0.0);
+            }
+        }
+    }
+
+    /// Switches to running the processor at `internal_rate`, from a clean state if that's a change.
+    pub fn set_internal_rate(&mut self, internal_rate: InternalRate)
+    {
+        if internal_rate != self.internal_rate
+        {
+            self.internal_rate = internal_rate;
+            self.processor.set_sample_rate(internal_rate.rate().unwrap_or(self.rate));
+            self.reset();
+        }
+    }
+
+    /// Half the span of the resampling kernels in seconds, between the host and a given internal rate.
+    fn half_width(rate: f64, internal_rate: f64) -> f64
+    {
+        HALF_TAPS as f64/rate.min(internal_rate)
+    }
+
+    /// Latency in host samples of a processor with `latency` samples of its own, at an internal rate.
+    fn latency_at(rate: f64, internal_rate: InternalRate, latency: usize) -> usize
+    {
+        match internal_rate.rate()
+        {
+            // Input and output kernels both look ahead by their half width
+            Some(internal_rate) => ((latency as f64/internal_rate + 2.0*Self::half_width(rate, internal_rate))*rate).ceil() as usize + 1,
+            None => latency
+        }
+    }
+
+    /// Upper bound on the latency in host samples at any internal rate, for a processor of at most `max_latency` samples.
+    pub fn max_latency(rate: f64, max_latency: usize) -> usize
+    {
+        InternalRate::VARIANTS.into_iter()
+            .map(|internal_rate| Self::latency_at(rate, internal_rate, max_latency))
+            .max()
+            .unwrap_or(max_latency)
+    }
+
+    /// Lengths of the input and output rings, long enough for the kernels at any internal rate.
+    fn ring_lengths(rate: f64) -> [usize; 2]
+    {
+        InternalRate::VARIANTS.into_iter()
+            .filter_map(InternalRate::rate)
+            .map(|internal_rate| {
+                let half_width = Self::half_width(rate, internal_rate);
+                // The output is read two half widths behind the newest processed sample, plus a sample either way for rounding
+                [(2.0*half_width*rate).ceil() as usize + 2, (4.0*half_width*internal_rate).ceil() as usize + 4]
+            }).fold([1, 1], |[input, output], [i, o]| [input.max(i), output.max(o)])
+    }
+
+    /// Windowed sinc weight of a sample `t` seconds away, resampling from `from` Hz with the band limited to `lower` Hz.
+    fn weight(&self, t: f64, from: f64, lower: f64) -> f64
+    {
+        let u = (t*lower).abs()*KERNEL_RESOLUTION as f64;
+        let i = u.floor() as usize;
+        if i + 1 >= self.kernel.len()
+        {
+            return 0.0
+        }
+        let f = u - i as f64;
+        let k = self.kernel[i] + (self.kernel[i + 1] - self.kernel[i])*f;
+        k*ROLLOFF*lower/from
+    }
+
+    /// Reads a ring of samples at `rate` at time `t` in seconds, from the samples that came before sample `end`.
+    fn read(&self, ring: &[f64], rate: f64, lower: f64, t: f64, end: u64) -> f64
+    {
+        let half_width = HALF_TAPS as f64/lower;
+        let first = ((t - half_width)*rate).ceil().max(0.0) as u64;
+        let last = (((t + half_width)*rate).floor().max(-1.0) + 1.0) as u64;
+        let first = first.max(end.saturating_sub(ring.len() as u64));
+        (first..last.min(end)).map(|n| ring[(n%ring.len() as u64) as usize]*self.weight(t - n as f64/rate, rate, lower))
+            .sum()
+    }
+
+    /// Takes a host sample in and runs the processor on every internal sample it makes available, analysing only or processing.
+    fn feed(&mut self, channel: usize, x: f64, internal_rate: f64, analyse: bool)
+    {
+        let lower = self.rate.min(internal_rate);
+        let half_width = Self::half_width(self.rate, internal_rate);
+        let (n, _) = self.count[channel];
+        let length = self.input[channel].len();
+        self.input[channel][(n%length as u64) as usize] = x;
+        self.count[channel].0 += 1;
+        let now = n as f64/self.rate;
+
+        // An internal sample can be made once the host samples its kernel reaches have all come in
+        while self.count[channel].1 as f64/internal_rate + half_width <= now
+        {
+            let k = self.count[channel].1;
+            let u = self.read(&self.input[channel], self.rate, lower, k as f64/internal_rate, n + 1);
+            let y = if analyse
+            {
+                self.processor.analyse_sample(internal_rate, channel, u);
+                0.0
+            }
+            else
+            {
+                self.processor.process_sample(internal_rate, channel, u)
+            };
+            let length = self.output[channel].len();
+            self.output[channel][(k%length as u64) as usize] = y;
+            self.count[channel].1 += 1;
+        }
+    }
+}
+
+impl<const C: usize> AudioProcessor for Resampled<C>
+{
+    fn set_sample_rate(&mut self, rate: f64)
+    {
+        self.rate = rate;
+        self.resize_rings();
+        self.count = [(0, 0); C];
+        self.processor.set_sample_rate(self.internal_rate.rate().unwrap_or(rate));
+    }
+
+    fn set_block_size(&mut self, size: usize)
+    {
+        let ratio = self.internal_rate.rate().map_or(1.0, |internal_rate| internal_rate/self.rate);
+        self.processor.set_block_size((size as f64*ratio).ceil() as usize + 1)
+    }
+
+    fn configure(&mut self, settings: &Settings)
+    {
+        self.processor.configure(settings)
+    }
+
+    fn latency(&self) -> usize
+    {
+        Self::latency_at(self.rate, self.internal_rate, self.processor.latency())
+    }
+
+    fn set_pitch(&mut self, octaves: f64)
+    {
+        self.processor.set_pitch(octaves)
+    }
+
+    fn set_pitch_modulation(&mut self, octaves: f64)
+    {
+        self.processor.set_pitch_modulation(octaves)
+    }
+
+    fn tail_size(&self) -> usize
+    {
+        let ratio = self.internal_rate.rate().map_or(1.0, |internal_rate| self.rate/internal_rate);
+        (self.processor.tail_size() as f64*ratio).ceil() as usize
+    }
+
+    fn reset(&mut self)
+    {
+        self.processor.reset();
+        for ring in self.input.iter_mut()
+            .chain(self.output.iter_mut())
+        {
+            ring.fill(0.0);
+        }
+        self.count = [(0, 0); C];
+    }
+
+    fn link_channels(&mut self)
+    {
+        self.processor.link_channels()
+    }
+
+    fn spectral_balance(&self, channel: usize) -> Option<[[f64; 3]; 2]>
+    {
+        self.processor.spectral_balance(channel)
+    }
+
+    fn process_sample(&mut self, rate: f64, channel: usize, x: f64) -> f64
+    {
+        if rate != self.rate
+        {
+            self.set_sample_rate(rate);
+            self.reset();
+        }
+        let Some(internal_rate) = self.internal_rate.rate() else {
+            return self.processor.process_sample(rate, channel, x)
+        };
+
+        self.feed(channel, x, internal_rate, false);
+        // Read back far enough behind that the output kernel only reaches samples already processed, landing on a whole
+        // number of host samples of latency
+        let now = (self.count[channel].0 - 1) as f64/self.rate;
+        let t = now - self.latency() as f64/self.rate + self.processor.latency() as f64/internal_rate;
+        self.read(&self.output[channel], internal_rate, self.rate.min(internal_rate), t, self.count[channel].1)
+    }
+
+    fn analyse_sample(&mut self, rate: f64, channel: usize, x: f64)
+    {
+        if rate != self.rate
+        {
+            self.set_sample_rate(rate);
+            self.reset();
+        }
+        match self.internal_rate.rate()
+        {
+            Some(internal_rate) => self.feed(channel, x, internal_rate, true),
+            None => self.processor.analyse_sample(rate, channel, x)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::{pitch_shifter::PitchShifter, processor::{AudioProcessor, Settings}, test_signals, transfer_function};
+
+    use super::{InternalRate, Resampled};
+
+    fn shifter(rate: f64, internal_rate: InternalRate) -> Resampled<1>
+    {
+        let mut shifter = Resampled::new(Box::new(PitchShifter::<1>::new(rate)), rate);
+        shifter.set_internal_rate(internal_rate);
+        shifter
+    }
+
+    #[test]
+    fn rate_changes_reuse_the_rings()
+    {
+        let mut shifter = shifter(44100.0, InternalRate::Rate48000);
+        let rings = |shifter: &Resampled<1>| [shifter.input[0].as_ptr(), shifter.output[0].as_ptr()];
+        let allocated = rings(&shifter);
+        for rate in [8000.0, 192000.0, 384000.0, 22050.0]
+        {
+            shifter.process_sample(rate, 0, 0.5);
+            assert!(rings(&shifter) == allocated, "reallocated the rings for {} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn host_rate_passes_straight_through()
+    {
+        let rate = 44100.0;
+        let mut resampled = shifter(rate, InternalRate::Host);
+        let mut plain = PitchShifter::<1>::new(rate);
+        resampled.set_pitch_semitones(7.0);
+        plain.set_pitch_semitones(7.0);
+        for x in test_signals::multi_tone(rate, &[(440.0, 0.5)], 4096)
+        {
+            assert_eq!(resampled.process_sample(rate, 0, x), plain.process_sample(rate, 0, x));
+        }
+        assert_eq!(resampled.latency(), plain.latency());
+    }
+
+    #[test]
+    fn output_is_delayed_by_the_latency()
+    {
+        let rate = 44100.0;
+        let mut shifter = shifter(rate, InternalRate::Rate48000);
+        shifter.configure(&Settings {
+            linear_phase: true,
+            ..Default::default()
+        });
+        let latency = shifter.latency();
+        let input = test_signals::multi_tone(rate, &[(1000.0, 0.5)], 16384);
+        let output: Vec<f64> = input.iter().map(|&x| shifter.process_sample(rate, 0, x)).collect();
+        for (x, y) in input.iter()
+            .zip(output[latency..].iter())
+            .skip(8192)
+        {
+            assert!((x - y).abs() < 0.01, "{} came out as {}", x, y);
+        }
+    }
+
+    #[test]
+    fn fixed_rate_sounds_the_same_at_any_host_rate()
+    {
+        let tones = [(1000.0, 0.3), (3000.0, 0.2)];
+        let ratio = 2.0f64.powf(5.0/12.0);
+        let levels = [44100.0, 96000.0].map(|rate| {
+            let mut shifter = shifter(rate, InternalRate::Rate48000);
+            shifter.set_pitch_semitones(5.0);
+            let length = rate as usize;
+            let output: Vec<f64> = test_signals::multi_tone(rate, &tones, 2*length).into_iter()
+                .map(|x| shifter.process_sample(rate, 0, x))
+                .collect();
+            // The second half, long settled
+            tones.map(|(frequency, _)| 20.0*(transfer_function::response(rate, &output[length..], frequency*ratio).norm()*2.0/length as f64).log10())
+        });
+        for (level44100, level96000) in levels[0].into_iter()
+            .zip(levels[1])
+        {
+            assert!((level44100 - level96000).abs() < 0.5, "{} dB at 44.1 kHz against {} dB at 96 kHz", level44100, level96000);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Deterministic signals for the tests to feed through the processors.
+
+use std::f64::consts::TAU;
+
+/// Sum of sines, each given as a frequency in Hz and an amplitude, all starting at zero phase.
+pub fn multi_tone(rate: f64, tones: &[(f64, f64)], length: usize) -> Vec<f64>
+{
+    (0..length).map(|n| tones.iter()
+            .map(|&(frequency, amplitude)| amplitude*(TAU*frequency*n as f64/rate).sin())
+            .sum()
+        ).collect()
+}
+
+/// A unit impulse at the first sample.
+pub fn impulse(length: usize) -> Vec<f64>
+{
+    (0..length).map(|n| if n == 0 {1.0} else {0.0})
+        .collect()
+}
+
+/// A unit step at the first sample.
+pub fn step(length: usize) -> Vec<f64>
+{
+    vec![1.0; length]
+}
+
+/// Sine sweeping exponentially from `start` to `end` Hz over its length, so it spends the same time in every octave.
+pub fn sweep(rate: f64, start: f64, end: f64, amplitude: f64, length: usize) -> Vec<f64>
+{
+    let duration = length as f64/rate;
+    let octaves = (end/start).ln();
+    (0..length).map(|n| {
+            let t = n as f64/rate;
+            // The integral of the instantaneous frequency
+            let phase = TAU*start*duration/octaves*((t/duration*octaves).exp() - 1.0);
+            amplitude*phase.sin()
+        }).collect()
+}
+
+/// Uniform white noise between `-amplitude` and `amplitude`, the same for the same `seed`.
+pub fn white_noise(seed: u64, amplitude: f64, length: usize) -> Vec<f64>
+{
+    // xorshift64*, which can't start from zero
+    let mut state = seed.max(1);
+    (0..length).map(|_| {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            let x = state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+            amplitude*(x as f64/(1u64 << 52) as f64 - 1.0)
+        }).collect()
+}
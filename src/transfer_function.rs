@@ -0,0 +1,41 @@
+//! Measures what a processor does to its input from its impulse response, for tests to check against the ideal.
+
+use num::Complex;
+
+/// How far a response strays from the ideal, at its worst over the frequencies measured.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deviation
+{
+    /// Largest magnitude error in dB.
+    pub magnitude: f64,
+    /// Largest phase error in radians.
+    pub phase: f64
+}
+
+/// The DFT of `impulse_response` at `frequency` Hz.
+pub fn response(rate: f64, impulse_response: &[f64], frequency: f64) -> Complex<f64>
+{
+    let omega = std::f64::consts::TAU*frequency/rate;
+    impulse_response.iter()
+        .enumerate()
+        .map(|(n, &h)| h*Complex::cis(-omega*n as f64))
+        .sum()
+}
+
+/// How far `impulse_response` is from a plain delay of `delay` samples at unity gain, over `frequencies` in Hz.
+pub fn deviation_from_delay(rate: f64, impulse_response: &[f64], delay: usize, frequencies: &[f64]) -> Deviation
+{
+    frequencies.iter()
+        .map(|&frequency| {
+            let omega = std::f64::consts::TAU*frequency/rate;
+            // Whatever's left once the delay is taken out
+            let error = response(rate, impulse_response, frequency)*Complex::cis(omega*delay as f64);
+            Deviation {
+                magnitude: 20.0*error.norm().log10(),
+                phase: error.arg()
+            }
+        }).fold(Deviation::default(), |worst, deviation| Deviation {
+            magnitude: worst.magnitude.max(deviation.magnitude.abs()),
+            phase: worst.phase.max(deviation.phase.abs())
+        })
+}
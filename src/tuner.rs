@@ -0,0 +1,91 @@
+//! Input pitch detection, for showing the incoming note.
+
+/// Threshold on the normalized difference below which a lag counts as a period.
+const THRESHOLD: f64 = 0.15;
+/// Mean power below which the input counts as silent and no pitch is reported.
+const SILENCE: f64 = 1e-8;
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Pitch detector running YIN over the last `N` samples pushed to it. Periods up to `N/2` samples can be detected.
+pub struct PitchDetector<const N: usize>
+{
+    buffer: [f64; N],
+    i: usize,
+    difference: Vec<f64>
+}
+
+impl<const N: usize> PitchDetector<N>
+{
+    pub fn new() -> Self
+    {
+        Self {
+            buffer: [0.0; N],
+            i: 0,
+            difference: Vec::with_capacity(N/2)
+        }
+    }
+
+    pub fn push(&mut self, x: f64)
+    {
+        self.buffer[self.i] = x;
+        self.i = (self.i + 1) % N;
+    }
+
+    /// The fundamental of the buffered input in Hz, or `None` if it's silent or unpitched.
+    ///
+    /// This is much heavier than [`push`](PitchDetector::push), so it's meant to be called once per block.
+    pub fn detect(&mut self, rate: f64) -> Option<f64>
+    {
+        let x: [f64; N] = core::array::from_fn(|j| self.buffer[(self.i + j) % N]);
+        let w = N/2;
+        if x.iter().map(|x| x*x).sum::<f64>() < SILENCE*N as f64
+        {
+            return None
+        }
+
+        // Cumulative mean normalized difference
+        self.difference.clear();
+        self.difference.push(1.0);
+        let mut sum = 0.0;
+        for tau in 1..w
+        {
+            let d = x[..w].iter()
+                .zip(x[tau..tau + w].iter())
+                .map(|(x0, x1)| (x0 - x1)*(x0 - x1))
+                .sum::<f64>();
+            sum += d;
+            self.difference.push(if sum > 0.0 {d*tau as f64/sum} else {1.0});
+        }
+
+        let mut tau = (2..w).find(|&tau| self.difference[tau] < THRESHOLD)?;
+        while tau + 1 < w && self.difference[tau + 1] < self.difference[tau]
+        {
+            tau += 1;
+        }
+
+        // Parabolic interpolation around the minimum for sub-sample accuracy
+        let [d0, d1, d2] = [tau - 1, tau, (tau + 1).min(w - 1)].map(|tau| self.difference[tau]);
+        let denominator = d0 - 2.0*d1 + d2;
+        let offset = if denominator.abs() > f64::EPSILON {(d0 - d2)/(2.0*denominator)} else {0.0};
+        Some(rate/(tau as f64 + offset))
+    }
+}
+
+impl<const N: usize> Default for PitchDetector<N>
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// Name of the nearest equal tempered note to `frequency` with A4 at 440 Hz, and how many cents off it is.
+pub fn note(frequency: f64) -> (String, f64)
+{
+    let midi = 69.0 + 12.0*(frequency/440.0).log2();
+    let nearest = midi.round();
+    let name = NOTE_NAMES[(nearest as i64).rem_euclid(12) as usize];
+    let octave = (nearest as i64).div_euclid(12) - 1;
+    (format!("{}{}", name, octave), (midi - nearest)*100.0)
+}
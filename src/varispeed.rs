@@ -0,0 +1,284 @@
+use std::f64::consts::PI;
+
+use real_time_fir_iir_filters::{iir::third::ThirdOrderButterworthFilter, Filter};
+
+use crate::{processor::{AudioProcessor, Settings}, CHANNEL_COUNT};
+
+/// Length in samples of the tape each channel plays back from.
+pub const TAPE_LENGTH: usize = 1 << 16;
+
+/// Time in seconds to crossfade over when the head has to jump.
+const SPLICE_TIME: f64 = 0.01;
+
+/// Reads `x` at a fractional position, with linear interpolation. Past the end reads as silence.
+fn read(x: &[f64], position: f64) -> f64
+{
+    let i = position.floor() as usize;
+    let t = position - i as f64;
+    let x0 = x.get(i).copied().unwrap_or(0.0);
+    let x1 = x.get(i + 1).copied().unwrap_or(0.0);
+    x0 + (x1 - x0)*t
+}
+
+/// Plays `input` back at `ratio` times its speed, like a tape machine, so the pitch and the length both change by the ratio.
+///
+/// Speeding up low-passes the input first, so nothing folds back over Nyquist.
+pub fn resample(rate: f64, input: &[f64], ratio: f64) -> Vec<f64>
+{
+    let input: Vec<f64> = if ratio > 1.0
+    {
+        let mut filter = ThirdOrderButterworthFilter::new(rate*PI/ratio);
+        input.iter()
+            .map(|&x| filter.filter(rate, x)[0])
+            .collect()
+    }
+    else
+    {
+        input.to_vec()
+    };
+    let length = (input.len() as f64/ratio).floor() as usize;
+    (0..length).map(|n| read(&input, n as f64*ratio))
+        .collect()
+}
+
+/// Pitch shifter playing the input back from a tape at the pitch ratio, without keeping the duration.
+///
+/// Running, the head drifts away from the input, behind it going down and toward it going up. Once it runs out of tape either way,
+/// it jumps half a tape back toward the middle with a short crossfade, so it's only a true varispeed between jumps. For a whole
+/// recording, [`resample`] changes the length instead.
+pub struct Varispeed<const C: usize = CHANNEL_COUNT>
+{
+    anti_alias_filter: [ThirdOrderButterworthFilter<f64>; C],
+    tape: [Vec<f64>; C],
+    /// Where the next input sample is written on each channel's tape.
+    write: [usize; C],
+    /// How far behind the input each channel's head is, in samples.
+    lag: [f64; C],
+    /// Lag of the head being faded out after a jump, and how far through the fade it is.
+    splice: [Option<(f64, f64)>; C],
+    pitch_mul: f64,
+    /// Pitch ratio of the modulation on top of `pitch_mul`.
+    modulation_mul: f64,
+    rate: f64
+}
+
+impl<const C: usize> Varispeed<C>
+{
+    pub fn new(rate: f64) -> Self
+    {
+        Self {
+            anti_alias_filter: [(); C].map(|()| ThirdOrderButterworthFilter::new(rate*PI)),
+            tape: [(); C].map(|()| vec![0.0; TAPE_LENGTH]),
+            write: [0; C],
+            lag: [0.0; C],
+            splice: [None; C],
+            pitch_mul: 1.0,
+            modulation_mul: 1.0,
+            rate
+        }
+    }
+
+    fn ratio(&self) -> f64
+    {
+        self.pitch_mul*self.modulation_mul
+    }
+
+    /// How far behind the input a channel's head is right now, in samples. This is the delay through it, drifting between zero
+    /// and a whole tape as it plays back faster or slower, and landing half a tape behind after each jump.
+    pub fn lag(&self, channel: usize) -> f64
+    {
+        self.lag[channel]
+    }
+
+    /// Moves the anti-alias cutoff under the pitch the input actually plays back at, modulation included.
+    fn set_anti_alias(&mut self)
+    {
+        let omega = self.rate*PI/self.ratio().max(1.0);
+        for filter in self.anti_alias_filter.iter_mut()
+        {
+            filter.omega = omega;
+        }
+    }
+
+    /// Reads a channel's tape at `lag` samples behind the last sample written.
+    fn play(&self, channel: usize, lag: f64) -> f64
+    {
+        let position = self.write[channel] as f64 - 1.0 - lag + TAPE_LENGTH as f64;
+        let i = position.floor() as usize;
+        let t = position - i as f64;
+        let tape = &self.tape[channel];
+        let x0 = tape[i%TAPE_LENGTH];
+        let x1 = tape[(i + 1)%TAPE_LENGTH];
+        x0 + (x1 - x0)*t
+    }
+}
+
+impl<const C: usize> AudioProcessor for Varispeed<C>
+{
+    fn set_sample_rate(&mut self, rate: f64)
+    {
+        self.rate = rate;
+        self.set_pitch(self.pitch_mul.log2());
+    }
+
+    fn configure(&mut self, _settings: &Settings) {}
+
+    /// Zero, since the delay isn't fixed. The head starts at the input and drifts up to a tape behind it, see [`lag`](Self::lag),
+    /// so it can't be compensated for.
+    fn latency(&self) -> usize
+    {
+        0
+    }
+
+    fn set_pitch(&mut self, octaves: f64)
+    {
+        self.pitch_mul = 2.0f64.powf(octaves);
+        self.set_anti_alias();
+    }
+
+    fn set_pitch_modulation(&mut self, octaves: f64)
+    {
+        self.modulation_mul = 2.0f64.powf(octaves);
+        self.set_anti_alias();
+    }
+
+    fn tail_size(&self) -> usize
+    {
+        TAPE_LENGTH
+    }
+
+    fn reset(&mut self)
+    {
+        for filter in self.anti_alias_filter.iter_mut()
+        {
+            *filter = ThirdOrderButterworthFilter::new(filter.omega);
+        }
+        for tape in self.tape.iter_mut()
+        {
+            tape.fill(0.0);
+        }
+        self.write = [0; C];
+        self.lag = [0.0; C];
+        self.splice = [None; C];
+    }
+
+    fn process_sample(&mut self, rate: f64, channel: usize, x: f64) -> f64
+    {
+        if rate != self.rate
+        {
+            self.set_sample_rate(rate);
+            self.reset();
+        }
+
+        let x = if self.ratio() > 1.0 {self.anti_alias_filter[channel].filter(self.rate, x)[0]} else {x};
+        let write = &mut self.write[channel];
+        self.tape[channel][*write] = x;
+        *write = (*write + 1)%TAPE_LENGTH;
+
+        // The head moves at the ratio while the input moves at one
+        let dlag = 1.0 - self.ratio();
+        let lag = self.lag[channel] + dlag;
+        let lag = if lag < 0.0 || lag > (TAPE_LENGTH - 2) as f64
+        {
+            // The old head holds at the end it ran into while it fades out
+            self.splice[channel] = Some((lag, 0.0));
+            (lag + TAPE_LENGTH as f64*0.5).rem_euclid((TAPE_LENGTH - 2) as f64)
+        }
+        else
+        {
+            lag
+        };
+        self.lag[channel] = lag;
+
+        let y = self.play(channel, lag);
+        match self.splice[channel]
+        {
+            Some((old_lag, fade)) => {
+                let old_lag = (old_lag + dlag).clamp(0.0, (TAPE_LENGTH - 2) as f64);
+                let y = self.play(channel, old_lag)*(1.0 - fade) + y*fade;
+                let fade = fade + 1.0/(SPLICE_TIME*self.rate);
+                self.splice[channel] = if fade < 1.0 {Some((old_lag, fade))} else {None};
+                y
+            },
+            None => y
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::f64::consts::TAU;
+
+    use crate::processor::AudioProcessor;
+
+    use super::{resample, Varispeed, TAPE_LENGTH};
+
+    const RATE: f64 = 44100.0;
+
+    fn sine(frequency: f64, length: usize) -> Vec<f64>
+    {
+        (0..length).map(|n| (TAU*frequency*n as f64/RATE).sin())
+            .collect()
+    }
+
+    /// Frequency of a tone from how often it crosses zero going up.
+    fn frequency(x: &[f64]) -> f64
+    {
+        let crossings = x.windows(2)
+            .filter(|x| x[0] < 0.0 && x[1] >= 0.0)
+            .count();
+        crossings as f64*RATE/x.len() as f64
+    }
+
+    fn rms(x: &[f64]) -> f64
+    {
+        (x.iter()
+            .map(|x| x*x)
+            .sum::<f64>()/x.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn resampling_changes_the_pitch_and_the_length()
+    {
+        let input = sine(441.0, 44100);
+        for ratio in [0.5, 2.0]
+        {
+            let output = resample(RATE, &input, ratio);
+            assert_eq!(output.len(), (input.len() as f64/ratio) as usize, "length at a ratio of {}", ratio);
+            let frequency = frequency(&output);
+            assert!((frequency/441.0 - ratio).abs() < 0.01, "{} Hz at a ratio of {}", frequency, ratio);
+        }
+    }
+
+    #[test]
+    fn head_lags_by_the_speed_difference()
+    {
+        let mut varispeed = Varispeed::<1>::new(RATE);
+        varispeed.set_pitch(-1.0);
+        let input = sine(441.0, 32768);
+        let output: Vec<f64> = input.iter()
+            .map(|&x| varispeed.process_sample(RATE, 0, x))
+            .collect();
+        // Half speed, so half a sample behind for every sample in, and the tone an octave down
+        assert!((varispeed.lag(0) - 0.5*input.len() as f64).abs() < 1e-6, "head is {} samples behind", varispeed.lag(0));
+        let frequency = frequency(&output[1024..]);
+        assert!((frequency/220.5 - 1.0).abs() < 0.02, "played back at {} Hz", frequency);
+    }
+
+    #[test]
+    fn modulation_moves_the_anti_alias_filter()
+    {
+        // Only the modulation takes it up an octave, which would fold this back down if it wasn't filtered first
+        let input = sine(18000.0, TAPE_LENGTH/2);
+        let mut varispeed = Varispeed::<1>::new(RATE);
+        varispeed.set_pitch(0.0);
+        varispeed.set_pitch_modulation(1.0);
+        let output: Vec<f64> = input.iter()
+            .map(|&x| varispeed.process_sample(RATE, 0, x))
+            .collect();
+        // Going up, the head jumps half a tape back straight away, and only reaches what was written after a quarter tape
+        let (input, output) = (rms(&input), rms(&output[TAPE_LENGTH/4 + 1024..]));
+        assert!(output < input*0.1, "{} out of {} folded back", output, input);
+    }
+}
@@ -0,0 +1,96 @@
+//! Bindings for running the [`PitchShifter`] core inside an `AudioWorkletProcessor`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{processor::AudioProcessor, PitchShifter, CHANNEL_COUNT};
+
+#[wasm_bindgen(js_name = PitchShifter)]
+pub struct WasmPitchShifter
+{
+    shifter: PitchShifter
+}
+
+#[wasm_bindgen(js_class = PitchShifter)]
+impl WasmPitchShifter
+{
+    #[wasm_bindgen(constructor)]
+    pub fn new(rate: f64) -> Self
+    {
+        let mut shifter = PitchShifter::new(rate);
+        shifter.set_block_size(128);
+        Self {
+            shifter
+        }
+    }
+
+    /// Sets the pitch shift in octaves.
+    pub fn set_pitch(&mut self, octaves: f64)
+    {
+        self.shifter.set_pitch(octaves);
+    }
+
+    pub fn reset(&mut self)
+    {
+        self.shifter.reset();
+    }
+
+    /// Processes one render quantum of `channel` in place, at the worklet's `sampleRate`. Leaves channels past the ones the shifter
+    /// has as they are, and returns whether it processed anything.
+    pub fn process(&mut self, rate: f64, channel: usize, samples: &mut [f32]) -> bool
+    {
+        if channel >= CHANNEL_COUNT
+        {
+            return false
+        }
+        for x in samples.iter_mut()
+        {
+            *x = self.shifter.process_sample(rate, channel, *x as f64) as f32;
+        }
+        true
+    }
+}
+
+/// Runs natively with `cargo test`, and in wasm32 with `wasm-bindgen-test`, see the README.
+#[cfg(test)]
+mod tests
+{
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::{processor::AudioProcessor, PitchShifter, CHANNEL_COUNT};
+
+    use super::WasmPitchShifter;
+
+    #[test]
+    fn render_quanta_match_the_core()
+    {
+        let rate = 48000.0;
+        let input: Vec<f32> = (0..4096).map(|n| (n as f32*0.05).sin()*0.5)
+            .collect();
+
+        let mut worklet = WasmPitchShifter::new(rate);
+        worklet.set_pitch(0.5);
+        let mut quanta = input.clone();
+        for quantum in quanta.chunks_mut(128)
+        {
+            assert!(worklet.process(rate, 0, quantum));
+        }
+
+        let mut shifter: PitchShifter = PitchShifter::new(rate);
+        shifter.set_pitch(0.5);
+        for (x, y) in input.iter()
+            .zip(quanta)
+        {
+            assert_eq!(shifter.process_sample(rate, 0, *x as f64) as f32, y);
+        }
+    }
+
+    #[test]
+    fn channels_out_of_range_are_left_alone()
+    {
+        let mut worklet = WasmPitchShifter::new(48000.0);
+        let mut quantum = [0.25f32; 128];
+        assert!(!worklet.process(48000.0, CHANNEL_COUNT, &mut quantum));
+        assert_eq!(quantum, [0.25; 128]);
+    }
+}